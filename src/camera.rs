@@ -0,0 +1,214 @@
+use crate::modules::{Matrix4, Vector3};
+
+/// Projection of a camera
+#[derive(Clone, Copy, Debug)]
+pub enum Projection {
+    /// The vertical field of view is fov radians
+    Perspective { fov: f64 },
+    /// The visible box has the height
+    Orthographic { height: f64 },
+}
+
+/// A camera in eye looking at center
+///
+/// The points between the near and the far planes are visible,
+/// the near plane has the depth 1 and the far plane -1
+/// in the normalized device coordinates
+/// (the closer points have the bigger depth like in the zbuffer)
+#[derive(Clone, Copy, Debug)]
+pub struct Camera {
+    pub eye: Vector3,
+    pub center: Vector3,
+    /// Vector that is vertical in the image
+    pub up: Vector3,
+
+    pub projection: Projection,
+    /// Width of the image divided by its height
+    pub aspect: f64,
+    /// Distance from the eye to the near plane
+    pub near: f64,
+    /// Distance from the eye to the far plane
+    pub far: f64,
+}
+
+impl Camera {
+    /// Camera with a perspective projection
+    /// in the origin looking at -z
+    pub fn perspective(fov: f64, aspect: f64, near: f64, far: f64) -> Self {
+        Self::new(Projection::Perspective { fov }, aspect, near, far)
+    }
+
+    /// Camera with an orthographic projection
+    /// in the origin looking at -z
+    pub fn orthographic(height: f64, aspect: f64, near: f64, far: f64) -> Self {
+        Self::new(Projection::Orthographic { height }, aspect, near, far)
+    }
+
+    fn new(projection: Projection, aspect: f64, near: f64, far: f64) -> Self {
+        Self {
+            eye: Vector3 {
+                x: 0.0,
+                y: 0.0,
+                z: 0.0,
+            },
+            center: Vector3 {
+                x: 0.0,
+                y: 0.0,
+                z: -1.0,
+            },
+            up: Vector3 {
+                x: 0.0,
+                y: 1.0,
+                z: 0.0,
+            },
+            projection,
+            aspect,
+            near,
+            far,
+        }
+    }
+
+    /// Move the camera to eye looking at center
+    pub fn look_at(mut self, eye: Vector3, center: Vector3, up: Vector3) -> Self {
+        self.eye = eye;
+        self.center = center;
+        self.up = up;
+        self
+    }
+
+    /// Matrix that convert the coordinates in the model
+    /// to the frame of the camera with the eye in the origin looking at -z
+    pub fn view(&self) -> Matrix4 {
+        // matrix_model_view puts the center in the origin
+        // and the eye in the z-axis
+        let distance = (self.eye - self.center).norm();
+        let t = Matrix4::new([
+            [1.0, 0.0, 0.0, 0.0],
+            [0.0, 1.0, 0.0, 0.0],
+            [0.0, 0.0, 1.0, -distance],
+            [0.0, 0.0, 0.0, 1.0],
+        ]);
+        t * matrix_model_view(self.eye, self.center, self.up)
+    }
+
+    /// Matrix that convert the coordinates in the frame of the camera
+    /// to the clip coordinates
+    ///
+    /// The visible points are in -w <= x, y, z <= w
+    pub fn projection(&self) -> Matrix4 {
+        let (n, f) = (self.near, self.far);
+        match self.projection {
+            Projection::Perspective { fov } => {
+                let y = 1.0 / (fov / 2.0).tan();
+                let x = y / self.aspect;
+                // w is the distance to the eye, -z
+                Matrix4::new([
+                    [x, 0.0, 0.0, 0.0],
+                    [0.0, y, 0.0, 0.0],
+                    [0.0, 0.0, (f + n) / (f - n), 2.0 * f * n / (f - n)],
+                    [0.0, 0.0, -1.0, 0.0],
+                ])
+            }
+            Projection::Orthographic { height } => {
+                let y = 2.0 / height;
+                let x = y / self.aspect;
+                Matrix4::new([
+                    [x, 0.0, 0.0, 0.0],
+                    [0.0, y, 0.0, 0.0],
+                    [0.0, 0.0, 2.0 / (f - n), (f + n) / (f - n)],
+                    [0.0, 0.0, 0.0, 1.0],
+                ])
+            }
+        }
+    }
+
+    /// Matrix that convert the coordinates in the model to the clip coordinates
+    ///
+    /// Transformation chain: Projection * View * Model * v
+    pub fn transform(&self) -> Matrix4 {
+        self.projection() * self.view()
+    }
+}
+
+/// Matrix that change the size and the position of the model
+///
+/// The model is mapped onto scree cube
+/// [position.x, position.x+size.x] * [position.y, position.y+size.y] * [position.z, position.z+size.z]
+pub fn matrix_viewport(position: Vector3, size: Vector3) -> Matrix4 {
+    let Vector3 { x, y, z } = position;
+    // w = width, h = height, d = depth
+    let Vector3 { x: w, y: h, z: d } = size;
+
+    Matrix4::new([
+        [w / 2.0, 0.0, 0.0, x + w / 2.0],
+        [0.0, h / 2.0, 0.0, y + h / 2.0],
+        [0.0, 0.0, d / 2.0, z + d / 2.0],
+        [0.0, 0.0, 0.0, 1.0],
+    ])
+}
+
+/// Matrix that convert the coordinate to the frame (center, i', j' k')
+/// Where eye is a point and the camera is in eye pointing to the center
+/// and the vector up is in vertical
+///
+/// eye is a point, center is a point, up is a vector
+pub fn matrix_model_view(eye: Vector3, center: Vector3, up: Vector3) -> Matrix4 {
+    // The problem is:
+    // The origin of the new frame is the point C (center)
+    // the point E (eye) is in the z-axis of the frame and
+    // the vector u (up) has the x coordinate equals to zero
+    // because it's in vertical
+
+    // We want a matrix M that v' = v * M where v is in standard basis
+    // and v' is in the basis (i', j', k')
+    //
+    // We know that
+    // | 1 |           | 0 |           | 0 |
+    // | 0 | = M * i', | 1 | = M * j', | 0 | = M * k'
+    // | 0 |           | 0 |           | 1 |
+    //
+    // Then M (that is unique because it convert basis) is:
+    // | i'x  i'y  i'z |
+    // | j'x  j'y  j'z |
+    // | k'x  k'y  k'z |
+    //
+    // For example, i' * M =
+    // | i'x  i'y  i'z | | i'x |   | ||i'||  |   | 1 |
+    // | j'x  j'y  j'z | | i'y | = | i' * j' | = | 0 |
+    // | k'x  k'y  k'z | | i'z |   | i' * k' |   | 0 |
+    //
+    // To convert the coordinates of the point P in the frame (O, i, j, k)
+    // to P' in the frame (C, i', j', k')
+    // first we move c to the origin O then we multiply by M
+    // P' = M * (P - C)
+    //
+    // It's the same that
+    // | P'x*r |   | i'x  i'y  i'z  0 | | 1  0  0  -cx |
+    // | P'y*r | = | j'x  j'y  j'z  0 | | 0  1  0  -cy |
+    // | P'z*r |   | k'x  k'y  k'z  0 | | 0  0  1  -cz |
+    // |   r   |   |  0    0    0   1 | | 0  0  0   1  |
+
+    // k' = CE / || CE ||
+    // i' = (u ^ k') / ||u ^ k'||
+    // j' = k' ^ i'
+
+    let k_ = (eye - center).normalize();
+    let i_ = up.cross(k_).normalize();
+    let j_ = k_.cross(i_).normalize(); // Don't need to be normalized
+
+    let m = Matrix4::new([
+        [i_.x, i_.y, i_.z, 0.0],
+        [j_.x, j_.y, j_.z, 0.0],
+        [k_.x, k_.y, k_.z, 0.0],
+        [0.0, 0.0, 0.0, 1.0],
+    ]);
+
+    let t = Matrix4::new([
+        [1.0, 0.0, 0.0, -center.x],
+        [0.0, 1.0, 0.0, -center.y],
+        [0.0, 0.0, 1.0, -center.z],
+        [0.0, 0.0, 0.0, 1.0],
+    ]);
+
+    m * t // ModelView
+}
@@ -0,0 +1,64 @@
+use std::error::Error;
+use std::fmt;
+use std::io;
+
+/// Error while loading a model, a material library or an image
+#[derive(Debug)]
+pub enum LoadError {
+    /// The file can't be opened or read
+    Io { path: String, error: io::Error },
+
+    /// The content of the file is invalid or isn't implemented
+    ///
+    /// The line is the number of the line (starting at 1) in text files
+    /// and None in binary files
+    Invalid {
+        path: String,
+        line: Option<usize>,
+        reason: String,
+    },
+}
+
+impl LoadError {
+    pub fn io(path: &str, error: io::Error) -> Self {
+        Self::Io {
+            path: path.to_string(),
+            error,
+        }
+    }
+
+    pub fn invalid(path: &str, line: Option<usize>, reason: impl Into<String>) -> Self {
+        Self::Invalid {
+            path: path.to_string(),
+            line,
+            reason: reason.into(),
+        }
+    }
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io { path, error } => write!(f, "{}: {}", path, error),
+            Self::Invalid {
+                path,
+                line: Some(line),
+                reason,
+            } => write!(f, "{}:{}: {}", path, line, reason),
+            Self::Invalid {
+                path,
+                line: None,
+                reason,
+            } => write!(f, "{}: {}", path, reason),
+        }
+    }
+}
+
+impl Error for LoadError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::Io { error, .. } => Some(error),
+            Self::Invalid { .. } => None,
+        }
+    }
+}
@@ -3,14 +3,346 @@ use std::convert::TryInto;
 use std::fs::File;
 use std::io::{BufReader, BufWriter, Read, Write};
 use std::io::{Seek, SeekFrom};
+use std::sync::Mutex;
+
+use crate::camera::matrix_viewport;
+use crate::error::LoadError;
+use crate::modules::{Color, HdrColor, Matrix4, Point, Vector2, Vector3, Vector4};
+use crate::shader::{ShadedFace, Shader};
+use crate::simd::{self, Lanes, LANES};
+
+/// Faces discarded by the rasterizer
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Cull {
+    /// Draw all faces
+    None,
+    /// Discard the faces that are facing away from the camera
+    Back,
+    /// Discard the faces that are facing the camera
+    Front,
+}
+
+/// Order of the vertices of a front face in the screen
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Winding {
+    CounterClockwise,
+    Clockwise,
+}
+
+impl Winding {
+    /// Winding of the mirror image of the triangle
+    pub fn reverse(self) -> Self {
+        match self {
+            Winding::CounterClockwise => Winding::Clockwise,
+            Winding::Clockwise => Winding::CounterClockwise,
+        }
+    }
+}
+
+/// Which faces the rasterizer discards
+/// using the winding order of the triangle in the screen
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CullMode {
+    pub cull: Cull,
+    pub front: Winding,
+}
+
+impl CullMode {
+    /// Test if a triangle is discarded
+    /// using its signed area in the screen
+    /// (positive if the vertices are in counterclockwise order)
+    pub fn is_culled(self, area: i32) -> bool {
+        let front = match self.front {
+            Winding::CounterClockwise => area > 0,
+            Winding::Clockwise => area < 0,
+        };
+        // A degenerate triangle has no pixels to draw anyway
+        match self.cull {
+            Cull::None => false,
+            Cull::Back => area != 0 && !front,
+            Cull::Front => area != 0 && front,
+        }
+    }
+}
 
-use crate::modules::{Color, Point, Vector2, Vector3};
+impl Default for CullMode {
+    /// Discard the back faces of counterclockwise triangles
+    /// like the faces in Wavefront obj files
+    fn default() -> Self {
+        Self {
+            cull: Cull::Back,
+            front: Winding::CounterClockwise,
+        }
+    }
+}
 
 // Using i32 because Point use i32
-pub struct Image {
+/// An image with 8-bit colors,
+/// or other type of pixels like the HdrColor of an HdrImage
+///
+/// A multisampled image keeps many samples of each pixel
+/// and must be resolved before saving it
+pub struct Image<P = Color> {
     pub width: i32,
     pub height: i32,
-    pixels: Vec<Color>,
+    /// Samples of each pixel
+    samples: usize,
+    /// The samples of the pixel i are in [i * samples, (i + 1) * samples)
+    pixels: Vec<P>,
+}
+
+/// Image with floating point colors used to render
+/// with high dynamic range,
+/// it's converted to an Image by the tone mapping
+pub type HdrImage = Image<HdrColor>;
+
+/// How the edges of the triangles are smoothed
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum AntiAliasing {
+    /// One sample in each pixel, the edges are aliased
+    #[default]
+    None,
+    /// Render the image n times bigger in each axis
+    /// and average each n x n block of pixels
+    Supersampling(u32),
+    /// Test the coverage and the depth of n samples in each pixel
+    /// but run the fragment stage once in each pixel
+    Multisampling(Samples),
+}
+
+/// Number of samples in each pixel with multisampling,
+/// the counts with a standard sample pattern
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Samples {
+    #[allow(dead_code)]
+    Two,
+    Four,
+    #[allow(dead_code)]
+    Eight,
+    #[allow(dead_code)]
+    Sixteen,
+}
+
+impl Samples {
+    pub fn count(self) -> usize {
+        match self {
+            Self::Two => 2,
+            Self::Four => 4,
+            Self::Eight => 8,
+            Self::Sixteen => 16,
+        }
+    }
+}
+
+/// Shape of the ends of a thick line
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LineCap {
+    /// The line ends in its end points
+    Butt,
+    /// The line is extended by half of its width
+    #[allow(dead_code)]
+    Square,
+    /// A half circle with the width as diameter
+    Round,
+}
+
+/// How a line is drawn
+#[derive(Clone, Debug, PartialEq)]
+pub struct LineStyle {
+    /// Width in pixels
+    width: f64,
+    cap: LineCap,
+    /// Alternating lengths in pixels of the dashes and the gaps,
+    /// the line is solid if it's empty
+    dash: Vec<f64>,
+    anti_aliased: bool,
+}
+
+/// Solid line with one pixel of width and without anti-aliasing
+/// like Image::line
+impl Default for LineStyle {
+    fn default() -> Self {
+        Self {
+            width: 1.0,
+            cap: LineCap::Butt,
+            dash: Vec::new(),
+            anti_aliased: false,
+        }
+    }
+}
+
+impl LineStyle {
+    pub fn with_width(mut self, width: f64) -> Self {
+        self.width = width.max(0.0);
+        self
+    }
+
+    pub fn with_cap(mut self, cap: LineCap) -> Self {
+        self.cap = cap;
+        self
+    }
+
+    /// Alternating lengths of the dashes and the gaps,
+    /// like [6.0, 3.0] for dashes or [0.0, 4.0] and round caps for dots
+    ///
+    /// The pattern starts again in each line
+    pub fn with_dash(mut self, dash: &[f64]) -> Self {
+        assert!(
+            dash.iter().all(|&length| length >= 0.0) && dash.iter().sum::<f64>() > 0.0,
+            "Invalid dash pattern: {:?}",
+            dash
+        );
+        self.dash = dash.to_vec();
+        self
+    }
+
+    /// Smooth the borders of the line with the coverage of each pixel
+    pub fn with_anti_aliasing(mut self, anti_aliased: bool) -> Self {
+        self.anti_aliased = anti_aliased;
+        self
+    }
+}
+
+/// Operator that compress the high dynamic range colors to [0, 1]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ToneMapping {
+    /// Clamp each channel, the bright colors are clipped
+    #[allow(dead_code)]
+    Clamp,
+    /// c / (1 + c)
+    #[allow(dead_code)]
+    Reinhard,
+    /// Narkowicz's fit of the ACES filmic curve
+    Aces,
+}
+
+impl ToneMapping {
+    fn apply(self, c: f32) -> f32 {
+        match self {
+            Self::Clamp => c,
+            Self::Reinhard => c / (1.0 + c),
+            Self::Aces => (c * (2.51 * c + 0.03)) / (c * (2.43 * c + 0.59) + 0.14),
+        }
+    }
+}
+
+/// Equation that combines the color of a fragment (source)
+/// with the color of the pixel (destination)
+///
+/// The channels and the alpha are in [0, 1]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BlendMode {
+    /// source * alpha + destination * (1 - alpha)
+    Alpha,
+    /// source * alpha + destination, used by lights like fire or glow
+    #[allow(dead_code)]
+    Additive,
+    /// source * destination, used to tint like colored glass
+    #[allow(dead_code)]
+    Multiply,
+}
+
+/// A type of pixel where the fragments can be blended
+///
+/// The rgba channels are linear (the 8-bit colors are divided by 255)
+pub trait Blend: Copy + Default {
+    fn to_rgba(self) -> [f32; 4];
+    fn from_rgba(rgba: [f32; 4]) -> Self;
+
+    /// Combine the fragment (self) with the pixel in the image
+    fn blend(self, destination: Self, mode: BlendMode) -> Self {
+        let [sr, sg, sb, sa] = self.to_rgba();
+        let [dr, dg, db, da] = destination.to_rgba();
+        let mix = |s: f32, d: f32| match mode {
+            BlendMode::Alpha => s * sa + d * (1.0 - sa),
+            BlendMode::Additive => s * sa + d,
+            BlendMode::Multiply => d * (s * sa + 1.0 - sa),
+        };
+        // The pixel is at least as opaque as before
+        let alpha = sa + da * (1.0 - sa);
+        Self::from_rgba([mix(sr, dr), mix(sg, dg), mix(sb, db), alpha])
+    }
+}
+
+impl Blend for Color {
+    fn to_rgba(self) -> [f32; 4] {
+        let channel = |c: u8| (c as f32) / 255.0;
+        [
+            channel(self.r),
+            channel(self.g),
+            channel(self.b),
+            channel(self.a),
+        ]
+    }
+
+    fn from_rgba([r, g, b, a]: [f32; 4]) -> Self {
+        let channel = |c: f32| (c.clamp(0.0, 1.0) * 255.0).round() as u8;
+        Self::rgba(channel(r), channel(g), channel(b), channel(a))
+    }
+}
+
+/// The HDR colors have no alpha, they are always opaque
+impl Blend for HdrColor {
+    fn to_rgba(self) -> [f32; 4] {
+        [self.r, self.g, self.b, 1.0]
+    }
+
+    fn from_rgba([r, g, b, _]: [f32; 4]) -> Self {
+        Self { r, g, b }
+    }
+}
+
+/// Buffers of the weighted blended order-independent transparency
+/// (McGuire and Bavoil, 2013)
+///
+/// The transparent fragments are summed in any order
+/// with a weight that is bigger for the fragments closer to the camera,
+/// then the weighted average is composited over the image
+pub struct OitBuffer {
+    /// Sum of (r * a * weight, g * a * weight, b * a * weight, a * weight)
+    accumulation: Vec<[f32; 4]>,
+    /// Product of (1 - a), the fraction of the pixel that is visible
+    revealage: Vec<f32>,
+}
+
+impl OitBuffer {
+    /// Buffers for each sample of the image
+    pub fn new<P>(image: &Image<P>) -> Self {
+        let size = image.pixels.len();
+        Self {
+            accumulation: vec![[0.0; 4]; size],
+            revealage: vec![1.0; size],
+        }
+    }
+
+    /// Add the fragment in the pixel i with the depth z of the zbuffer
+    fn add(&mut self, i: usize, [r, g, b, a]: [f32; 4], z: f64) {
+        // Distance in [0, 1] where 0 is the near plane
+        let distance = (1.0 - z / DEPTH).clamp(0.0, 1.0) as f32;
+        let weight = a * (0.03 / (1e-5 + distance.powi(4))).clamp(1e-2, 3e3);
+
+        let [ar, ag, ab, aa] = &mut self.accumulation[i];
+        *ar += r * a * weight;
+        *ag += g * a * weight;
+        *ab += b * a * weight;
+        *aa += a * weight;
+        self.revealage[i] *= 1.0 - a;
+    }
+
+    /// Composite the transparent fragments over the image
+    /// (the same image used to create the buffers)
+    pub fn resolve<P: Blend>(&self, image: &mut Image<P>) {
+        for (i, pixel) in image.pixels.iter_mut().enumerate() {
+            let revealage = self.revealage[i];
+            if revealage >= 1.0 {
+                continue;
+            }
+            let [r, g, b, weight] = self.accumulation[i];
+            let weight = weight.max(1e-5);
+            let average = P::from_rgba([r / weight, g / weight, b / weight, 1.0 - revealage]);
+            *pixel = average.blend(*pixel, BlendMode::Alpha);
+        }
+    }
 }
 
 /// Test if the point p is inside triangle v0 v1 v2
@@ -18,6 +350,273 @@ fn inside_triangle(p: Point, triangle: (Point, Point, Point)) -> bool {
     inside_triangle_barycentric(Point::barycentric(p, triangle))
 }
 
+/// Depth of the zbuffer
+///
+/// The points in the near plane have the depth DEPTH
+/// and the points in the far plane have the depth 0
+pub const DEPTH: f64 = 255.0;
+
+/// Positions of the samples of a pixel from its center
+/// (the standard multisample patterns of Direct3D)
+///
+/// The pixel (x, y) is the square with the center (x, y)
+/// and the side 1
+fn sample_pattern(samples: usize) -> &'static [(f64, f64)] {
+    const fn pattern<const N: usize>(positions: [(i8, i8); N]) -> [(f64, f64); N] {
+        let mut result = [(0.0, 0.0); N];
+        let mut i = 0;
+        while i < N {
+            result[i] = (positions[i].0 as f64 / 16.0, positions[i].1 as f64 / 16.0);
+            i += 1;
+        }
+        result
+    }
+    const ONE: [(f64, f64); 1] = [(0.0, 0.0)];
+    const TWO: [(f64, f64); 2] = pattern([(4, 4), (-4, -4)]);
+    const FOUR: [(f64, f64); 4] = pattern([(-2, -6), (6, -2), (-6, 2), (2, 6)]);
+    const EIGHT: [(f64, f64); 8] = pattern([
+        (1, -3),
+        (-1, 3),
+        (5, 1),
+        (-3, -5),
+        (-5, 5),
+        (-7, -1),
+        (3, 7),
+        (7, -7),
+    ]);
+    const SIXTEEN: [(f64, f64); 16] = pattern([
+        (1, 1),
+        (-1, -3),
+        (-3, 2),
+        (4, -1),
+        (-5, -2),
+        (2, 5),
+        (5, 3),
+        (3, -5),
+        (-2, 6),
+        (0, -7),
+        (-4, -6),
+        (-6, 4),
+        (-8, 0),
+        (7, -4),
+        (6, 7),
+        (-7, -8),
+    ]);
+
+    match samples {
+        1 => &ONE,
+        2 => &TWO,
+        4 => &FOUR,
+        8 => &EIGHT,
+        16 => &SIXTEEN,
+        _ => panic!("Invalid number of samples: {}", samples),
+    }
+}
+
+/// Size of the guard band around the image in image sizes
+///
+/// The triangles are clipped against the sides of the guard band
+/// instead of the sides of the image, because the vertices are rounded
+/// to pixels and the new vertices of two triangles with a common edge
+/// can open a crack between the triangles.
+/// The pixels outside the image are skipped by the rasterizer anyway
+const GUARD_BAND: f64 = 1.0;
+
+/// A vertex of a clipped triangle:
+/// the homogeneous coordinates (x, y, z, w)
+/// and the barycentric coordinates in the original triangle
+type ClipVertex = ([f64; 4], [f64; 3]);
+
+/// A plane (normal, offset) where a vertex v is inside if normal * v + offset >= 0
+type ClipPlane = ([f64; 4], f64);
+
+/// Planes of the view frustum in clip coordinates
+/// where a point (x, y, z, w) is visible if -w <= z <= w,
+/// (x/w, y/w) is inside the image and its guard band
+/// and w is positive (the point isn't behind the eye)
+const CLIP_PLANES: [ClipPlane; 7] = {
+    const SIDE: f64 = 1.0 + 2.0 * GUARD_BAND;
+    [
+        ([0.0, 0.0, -1.0, 1.0], 0.0),
+        ([0.0, 0.0, 1.0, 1.0], 0.0),
+        ([0.0, 0.0, 0.0, 1.0], -f64::EPSILON),
+        ([1.0, 0.0, 0.0, SIDE], 0.0),
+        ([-1.0, 0.0, 0.0, SIDE], 0.0),
+        ([0.0, 1.0, 0.0, SIDE], 0.0),
+        ([0.0, -1.0, 0.0, SIDE], 0.0),
+    ]
+};
+
+/// Clip a convex polygon against a plane
+/// using the Sutherland–Hodgman algorithm
+///
+/// The new vertices are interpolated in homogeneous coordinates
+fn clip_polygon(polygon: Vec<ClipVertex>, (normal, offset): ClipPlane) -> Vec<ClipVertex> {
+    let distance = |(v, _): &ClipVertex| {
+        normal[0] * v[0] + normal[1] * v[1] + normal[2] * v[2] + normal[3] * v[3] + offset
+    };
+
+    let mut result: Vec<ClipVertex> = Vec::new();
+    for (i, current) in polygon.iter().enumerate() {
+        let next = &polygon[(i + 1) % polygon.len()];
+        let d0 = distance(current);
+        let d1 = distance(next);
+
+        if d0 >= 0.0 {
+            result.push(*current);
+        }
+
+        // The edge crosses the plane
+        if (d0 >= 0.0) != (d1 >= 0.0) {
+            let t = d0 / (d0 - d1);
+            let (c0, b0) = current;
+            let (c1, b1) = next;
+            result.push((
+                std::array::from_fn(|k| c0[k] + (c1[k] - c0[k]) * t),
+                std::array::from_fn(|k| b0[k] + (b1[k] - b0[k]) * t),
+            ));
+        }
+    }
+    result
+}
+
+/// Clip the triangle in clip coordinates against the view frustum
+/// and map the clipped polygon to an image with the size (width, height)
+/// keeping the w of each vertex
+fn clip_triangle(
+    triangle: (Vector4, Vector4, Vector4),
+    width: i32,
+    height: i32,
+) -> Vec<ClipVertex> {
+    let (v0, v1, v2) = triangle;
+
+    let mut polygon: Vec<ClipVertex> = vec![
+        (v0.to_array(), [1.0, 0.0, 0.0]),
+        (v1.to_array(), [0.0, 1.0, 0.0]),
+        (v2.to_array(), [0.0, 0.0, 1.0]),
+    ];
+    for plane in CLIP_PLANES {
+        polygon = clip_polygon(polygon, plane);
+    }
+
+    // Viewport transformation, that keeps the w
+    let width = width as f64;
+    let height = height as f64;
+    for ([x, y, z, w], _) in &mut polygon {
+        *x = (*x + *w) * width / 2.0;
+        *y = (*y + *w) * height / 2.0;
+        *z = (*z + *w) * DEPTH / 2.0;
+    }
+    polygon
+}
+
+/// Rows of each tile of the tiled rasterizer,
+/// a tile has all the columns of the image
+const TILE_ROWS: i32 = 16;
+
+/// Number of threads of the CPU, or 1 if it's unknown
+pub fn available_threads() -> usize {
+    std::thread::available_parallelism().map_or(1, |threads| threads.get())
+}
+
+/// Write the fragment in the pixel and its depth in the zbuffer
+fn write_opaque<P>(pixel: &mut P, depth: &mut f64, _: usize, z: f64, color: P) {
+    *pixel = color;
+    *depth = z;
+}
+
+/// Bits of the fraction of the positions in the rasterizer,
+/// the vertices are rounded to 1/256 of pixel
+const SUBPIXEL_BITS: u32 = 8;
+const SUBPIXEL: i64 = 1 << SUBPIXEL_BITS;
+
+/// Coordinate rounded to fixed point with subpixel precision
+fn to_fixed(c: f64) -> i64 {
+    (c * SUBPIXEL as f64).round() as i64
+}
+
+/// Samples of the pattern in fixed point from the point of the pixel
+fn fixed_pattern(samples: usize) -> impl Iterator<Item = (i64, i64)> + Clone {
+    sample_pattern(samples).iter().map(|&(dx, dy)| {
+        let f = |d: f64| (d * SUBPIXEL as f64) as i64;
+        (f(dx), f(dy))
+    })
+}
+
+/// First and last pixels with samples that can be between
+/// the fixed point coordinates min and max,
+/// the samples are from min_d to max_d from the point of the pixel
+fn covered_pixels(min: i64, max: i64, (min_d, max_d): (i64, i64)) -> (i64, i64) {
+    let ceil = |c: i64| -(-c).div_euclid(SUBPIXEL);
+    let floor = |c: i64| c.div_euclid(SUBPIXEL);
+    (ceil(min - max_d), floor(max - min_d))
+}
+
+/// Smallest and biggest offset of the samples in an axis
+fn extent(offsets: impl Iterator<Item = i64>) -> (i64, i64) {
+    offsets.fold((0, 0), |(a, b), d| (d.min(a), d.max(b)))
+}
+
+/// Edge function of the edge from a to b of a triangle in fixed point
+///
+/// The value in p is a * p.x + b * p.y + c,
+/// twice the signed area of the triangle a b p,
+/// positive at the inside of the triangle
+#[derive(Clone, Copy)]
+struct Edge {
+    a: i64,
+    b: i64,
+    c: i64,
+    /// The points over the edge are drawn
+    /// only if it's a top or left edge (top-left fill rule),
+    /// so an edge shared by two triangles is drawn once
+    top_left: bool,
+}
+
+impl Edge {
+    /// Edge from a to b with the sign of the area of the triangle,
+    /// -1 if the vertices are in clockwise order
+    fn new(a: (i64, i64), b: (i64, i64), sign: i64) -> Self {
+        let dx = (b.0 - a.0) * sign;
+        let dy = (b.1 - a.1) * sign;
+        Self {
+            a: -dy,
+            b: dx,
+            c: dy * a.0 - dx * a.1,
+            // The inside is at the left of the edge and y grows upwards
+            top_left: dy < 0 || (dy == 0 && dx < 0),
+        }
+    }
+
+    fn at(self, x: i64, y: i64) -> i64 {
+        self.a * x + self.b * y + self.c
+    }
+
+    /// The points inside have the edge function greater than the threshold,
+    /// the function is an integer then the points over a top-left edge are inside
+    fn threshold(self) -> f64 {
+        if self.top_left {
+            -1.0
+        } else {
+            0.0
+        }
+    }
+}
+
+/// Rows of an image and its zbuffer where the rasterizer draws,
+/// the whole image or a tile of the tiled rasterizer
+struct Target<'a, P> {
+    /// Size of the whole image
+    width: i32,
+    height: i32,
+    samples: usize,
+    /// First and last rows
+    rows: (i32, i32),
+    /// Samples of the rows
+    pixels: &'a mut [P],
+    zbuffer: &'a mut [f64],
+}
+
 /// Test if the point is inside the triangle using the barycentric coordinates
 ///
 /// For the point p and the triangle v0 v1 v2 do this:
@@ -29,28 +628,283 @@ fn inside_triangle_barycentric(bary: Option<(f64, f64, f64)>) -> bool {
     }
 }
 
-impl Image {
+impl<P: Copy> Target<'_, P> {
+    /// Clip and draw the triangle in clip coordinates,
+    /// the fragments that pass the depth test are written by the function
+    /// write(sample, depth in the zbuffer, index of the sample in the image, z, color)
+    fn triangle<S, F>(
+        &mut self,
+        triangle: (Vector4, Vector4, Vector4),
+        shader: &S,
+        cull_mode: CullMode,
+        write: &mut F,
+    ) where
+        S: Shader<P>,
+        F: FnMut(&mut P, &mut f64, usize, f64, P),
+    {
+        let polygon = clip_triangle(triangle, self.width, self.height);
+
+        // The clipped polygon is convex, so it's split in a fan
+        for i in 2..polygon.len() {
+            let triangle = (polygon[0], polygon[i - 1], polygon[i]);
+            self.triangle_clipped(triangle, shader, cull_mode, write);
+        }
+    }
+
+    /// Draw a triangle that is inside the view frustum
+    ///
+    /// The barycentric coordinates of each vertex in the original triangle
+    /// are used to convert the barycentric coordinates of the pixels
+    /// before calling the fragment stage
+    fn triangle_clipped<S, F>(
+        &mut self,
+        triangle: (ClipVertex, ClipVertex, ClipVertex),
+        shader: &S,
+        cull_mode: CullMode,
+        write: &mut F,
+    ) where
+        S: Shader<P>,
+        F: FnMut(&mut P, &mut f64, usize, f64, P),
+    {
+        #[cfg(target_arch = "x86_64")]
+        {
+            if simd::has_avx() {
+                // Safe because the processor has AVX
+                unsafe { self.triangle_clipped_avx(triangle, shader, cull_mode, write) };
+                return;
+            }
+        }
+        self.triangle_lanes(
+            triangle,
+            shader,
+            cull_mode,
+            write,
+            simd::Triangle::fragments,
+        );
+    }
+
+    /// Draw the triangle like triangle_clipped
+    /// with all the rasterizer compiled for AVX
+    #[cfg(target_arch = "x86_64")]
+    #[target_feature(enable = "avx")]
+    unsafe fn triangle_clipped_avx<S, F>(
+        &mut self,
+        triangle: (ClipVertex, ClipVertex, ClipVertex),
+        shader: &S,
+        cull_mode: CullMode,
+        write: &mut F,
+    ) where
+        S: Shader<P>,
+        F: FnMut(&mut P, &mut f64, usize, f64, P),
+    {
+        self.triangle_lanes(
+            triangle,
+            shader,
+            cull_mode,
+            write,
+            |lanes, edges, depths| lanes.fragments_avx(edges, depths),
+        );
+    }
+
+    /// Draw the triangle testing LANES pixels of a row together
+    /// with the function that finds the fragments of the lanes
+    #[inline(always)]
+    fn triangle_lanes<S, F, L>(
+        &mut self,
+        triangle: (ClipVertex, ClipVertex, ClipVertex),
+        shader: &S,
+        cull_mode: CullMode,
+        write: &mut F,
+        fragments_of: L,
+    ) where
+        S: Shader<P>,
+        F: FnMut(&mut P, &mut f64, usize, f64, P),
+        L: Fn(&simd::Triangle, [f64; 3], &Lanes) -> simd::Fragments,
+    {
+        let ((c0, b0), (c1, b1), (c2, b2)) = triangle;
+
+        // Perspective division
+        let divide = |[x, y, z, w]: [f64; 4]| Vector3 {
+            x: x / w,
+            y: y / w,
+            z: z / w,
+        };
+        let v0 = divide(c0);
+        let v1 = divide(c1);
+        let v2 = divide(c2);
+
+        let w = self.width as usize;
+        let n = self.samples;
+        // Index in the image of the first sample of the rows
+        let first = self.rows.0 as usize * w * n;
+        let index = |i: usize, j: usize| (i * w + j) * n - first;
+
+        // Vertices in fixed point with subpixel precision
+        let fixed = |v: Vector3| (to_fixed(v.x), to_fixed(v.y));
+        let (f0, f1, f2) = (fixed(v0), fixed(v1), fixed(v2));
+
+        // The fan of a clipped triangle keeps its winding order
+        let area = (f1.0 - f0.0) * (f2.1 - f0.1) - (f1.1 - f0.1) * (f2.0 - f0.0);
+        if cull_mode.is_culled(area.signum() as i32) || area == 0 {
+            return;
+        }
+        // The edges of a clockwise triangle are reversed to be positive inside
+        let sign = area.signum();
+        let area = area.abs() as f64;
+        let edges = [
+            Edge::new(f1, f2, sign),
+            Edge::new(f2, f0, sign),
+            Edge::new(f0, f1, sign),
+        ];
+
+        // Find the pixels with samples that can be in the triangle
+        let pattern = fixed_pattern(n);
+        let (min_x, max_x) = covered_pixels(
+            f0.0.min(f1.0).min(f2.0),
+            f0.0.max(f1.0).max(f2.0),
+            extent(pattern.clone().map(|p| p.0)),
+        );
+        let (min_y, max_y) = covered_pixels(
+            f0.1.min(f1.1).min(f2.1),
+            f0.1.max(f1.1).max(f2.1),
+            extent(pattern.clone().map(|p| p.1)),
+        );
+        let (min_x, max_x) = (min_x.max(0), max_x.min(self.width as i64 - 1));
+        let min_y = min_y.max(self.rows.0 as i64);
+        let max_y = max_y.min(self.rows.1 as i64);
+        if min_x > max_x || min_y > max_y {
+            return;
+        }
+
+        // Change of each edge function to each sample of the pixel
+        let offsets = pattern.map(|(dx, dy)| edges.map(|e| e.a * dx + e.b * dy));
+        let step_x = edges.map(|e| (e.a * SUBPIXEL) as f64);
+        let step_y = edges.map(|e| (e.b * SUBPIXEL) as f64);
+
+        let lanes = simd::Triangle {
+            thresholds: edges.map(Edge::threshold),
+            steps: step_x.map(|step| std::array::from_fn(|l| l as f64 * step)),
+            z: [v0.z, v1.z, v2.z],
+            inverse_area: 1.0 / area,
+            inverse_w: [1.0 / c0[3], 1.0 / c1[3], 1.0 / c2[3]],
+            original: [b0, b1, b2],
+        };
+
+        // Edge functions in the point of the first pixel of the row
+        let mut row = edges.map(|e| e.at(min_x * SUBPIXEL, min_y * SUBPIXEL));
+
+        // Draw the samples in the box that are in the triangle,
+        // LANES pixels of the row are tested together
+        // and the edge functions are updated by steps from pixel to pixel
+        let mut samples: Vec<[f64; 3]> = Vec::with_capacity(n);
+        for y in min_y..=max_y {
+            // Edge functions of each sample in the first lane
+            samples.clear();
+            samples.extend(
+                offsets
+                    .clone()
+                    .map(|offset| std::array::from_fn(|j| (row[j] + offset[j]) as f64)),
+            );
+
+            for x in (min_x..=max_x).step_by(LANES) {
+                let pixels = (max_x - x + 1).min(LANES as i64) as usize;
+                let i = index(y as usize, x as usize);
+                // Color of the fragment of each lane
+                // in the first sample that passes the depth test
+                let mut fragments: [Option<Option<P>>; LANES] = [None; LANES];
+
+                for (k, sample) in samples.iter_mut().enumerate() {
+                    let depths: Lanes = if n == 1 && pixels == LANES {
+                        self.zbuffer[i..i + LANES].try_into().unwrap()
+                    } else {
+                        // The lanes after the last pixel never pass the depth test
+                        std::array::from_fn(|l| {
+                            if l < pixels {
+                                self.zbuffer[i + l * n + k]
+                            } else {
+                                f64::INFINITY
+                            }
+                        })
+                    };
+                    let lane_fragments = fragments_of(&lanes, *sample, &depths);
+
+                    let mut mask = lane_fragments.mask;
+                    while mask != 0 {
+                        let l = mask.trailing_zeros() as usize;
+                        mask &= mask - 1;
+                        let e: [f64; 3] = std::array::from_fn(|j| sample[j] + lanes.steps[j][l]);
+                        let bary = lane_fragments.bary;
+                        let bary = (bary[0][l], bary[1][l], bary[2][l]);
+                        let color = *fragments[l].get_or_insert_with(|| {
+                            let next = |step: [f64; 3]| {
+                                lanes.barycentric([e[0] + step[0], e[1] + step[1], e[2] + step[2]])
+                            };
+                            shader.fragment_footprint(bary, (next(step_x), next(step_y)))
+                        });
+                        if let Some(color) = color {
+                            let s = i + l * n + k;
+                            write(
+                                &mut self.pixels[s],
+                                &mut self.zbuffer[s],
+                                s + first,
+                                lane_fragments.z[l],
+                                color,
+                            );
+                        }
+                    }
+
+                    for (j, e) in sample.iter_mut().enumerate() {
+                        *e += LANES as f64 * step_x[j];
+                    }
+                }
+            }
+            for (j, e) in row.iter_mut().enumerate() {
+                *e += edges[j].b * SUBPIXEL;
+            }
+        }
+    }
+}
+
+impl<P: Copy + Default> Image<P> {
     /// Create a new image with all pixels with the color black
     pub fn new(width: i32, height: i32) -> Self {
-        let size = (width * height) as usize;
+        Self::multisample(width, height, 1)
+    }
+
+    /// Create a new image with all samples of the pixels with the color black
+    ///
+    /// The samples must be 1, 2, 4, 8 or 16
+    pub fn multisample(width: i32, height: i32, samples: usize) -> Self {
+        sample_pattern(samples);
+        let size = (width * height) as usize * samples;
         Self {
             width,
             height,
-            pixels: vec![Color::hex(b"#000"); size],
+            samples,
+            pixels: vec![P::default(); size],
         }
     }
 
+    /// A zbuffer with a depth for each sample of the image
+    /// filled with f64::NEG_INFINITY
+    pub fn zbuffer(&self) -> Vec<f64> {
+        vec![f64::NEG_INFINITY; self.pixels.len()]
+    }
+
     /// Set the value of pixel at (p.x, p.y) to color
-    pub fn set(&mut self, Point { x, y }: Point, color: Color) {
+    /// (all the samples of the pixel)
+    pub fn set(&mut self, Point { x, y }: Point, color: P) {
         if 0 <= x && x < self.width && 0 <= y && y < self.height {
-            self.pixels[(y * self.width + x) as usize] = color;
+            let i = (y * self.width + x) as usize * self.samples;
+            self.pixels[i..i + self.samples].fill(color);
         }
     }
 
     /// Get the color of pixel at (p.x, p.y)
-    pub fn get(&self, Point { x, y }: Point) -> Option<Color> {
+    /// (the first sample of the pixel)
+    pub fn get(&self, Point { x, y }: Point) -> Option<P> {
         if 0 <= x && x < self.width && 0 <= y && y < self.height {
-            Some(self.pixels[(y * self.width + x) as usize])
+            Some(self.pixels[(y * self.width + x) as usize * self.samples])
         } else {
             None
         }
@@ -58,38 +912,34 @@ impl Image {
 
     /// Set all image's pixels to color
     #[allow(dead_code)]
-    pub fn clear(mut self, color: Color) -> Self {
+    pub fn clear(mut self, color: P) -> Self {
         self.pixels.iter_mut().for_each(|i| *i = color);
         self
     }
 
     /// Flip the image vertically
     pub fn flip_vertically(mut self) -> Self {
-        let mut pixels: Vec<Color> = Vec::new();
-        for y in (0..self.height).rev() {
-            for x in 0..self.width {
-                pixels.push(self.get(Point { x, y }).unwrap());
-            }
-        }
-        self.pixels = pixels;
+        let row = self.width as usize * self.samples;
+        self.pixels = self.pixels.chunks(row).rev().flatten().copied().collect();
         self
     }
 
     /// Flip the image horizontally
     pub fn flip_horizontally(mut self) -> Self {
-        let mut pixels: Vec<Color> = Vec::new();
-        for y in 0..self.height {
-            for x in (0..self.width).rev() {
-                pixels.push(self.get(Point { x, y }).unwrap());
-            }
-        }
-        self.pixels = pixels;
+        let row = self.width as usize * self.samples;
+        let samples = self.samples;
+        self.pixels = self
+            .pixels
+            .chunks(row)
+            .flat_map(|row| row.chunks(samples).rev().flatten())
+            .copied()
+            .collect();
         self
     }
 
     /// Draw the triangle defined by the points v0, v1, v2
     /// filled with color
-    pub fn triangle(&mut self, triangle: (Point, Point, Point), color: Color) {
+    pub fn triangle(&mut self, triangle: (Point, Point, Point), color: P) {
         let (p0, p1, p2) = triangle;
         let max_x = p0.x.max(p1.x).max(p2.x);
         let max_y = p0.y.max(p1.y).max(p2.y);
@@ -107,210 +957,131 @@ impl Image {
     }
 
     /// Draw a triangle defined by the vertices v0, v1, v2
-    /// filled with color
+    /// colored by the fragment stage of the shader
     /// using a zbuffer to prevent drawing a hidden triangle over other
     ///
-    /// zbuffer length must be image.width * image.height
-    /// and be filled with f64::NEG_INFINITY
-    pub fn triangle_zbuffer(
+    /// The vertices are Matrices 4x1 in clip coordinates,
+    /// the triangle is clipped against the view frustum
+    /// before the perspective division,
+    /// mapped to the image by the viewport
+    /// and discarded if the cull mode culls its winding order
+    ///
+    /// zbuffer must have a depth for each sample of the image
+    /// and be filled with f64::NEG_INFINITY (see Image::zbuffer)
+    ///
+    /// In a multisampled image the coverage and the depth are tested
+    /// in each sample, but the fragment stage runs once in each pixel
+    pub fn triangle_shader<S: Shader<P>>(
         &mut self,
-        zbuffer: &mut Vec<f64>,
-        triangle: (Vector3, Vector3, Vector3),
-        color: Color,
+        zbuffer: &mut [f64],
+        triangle: (Vector4, Vector4, Vector4),
+        shader: &S,
+        cull_mode: CullMode,
     ) {
-        let (v0, v1, v2) = triangle;
-        let w = self.width as usize;
-        let index = |i: usize, j: usize| i * (w as usize) + j;
-
-        // Convert vertices to points in image
-        let p0 = v0.to_point();
-        let p1 = v1.to_point();
-        let p2 = v2.to_point();
-
-        // Find a box where that fit the triangle
-        let max_x = (p0.x.max(p1.x).max(p2.x) + 10).min(self.width - 1);
-        let max_y = (p0.y.max(p1.y).max(p2.y) + 10).min(self.height - 1);
-        let min_x = (p0.x.min(p1.x).min(p2.x) - 10).max(0);
-        let min_y = (p0.y.min(p1.y).min(p2.y) - 10).max(0);
-
-        // Draw the pixels in the box that are in the triangle
-        for x in min_x..=max_x {
-            for y in min_y..=max_y {
-                let p = Point { x, y };
-                let bary = Point::barycentric(p, (p0, p1, p2));
-                if inside_triangle_barycentric(bary) {
-                    let z = Vector3::lerp(bary, (v0, v1, v2)).unwrap().z;
-                    let i = index(y as usize, x as usize);
-                    if i < zbuffer.len() as usize && zbuffer[i] < z {
-                        zbuffer[i] = z;
-                        self.set(p, color);
-                    }
-                }
-            }
-        }
+        self.triangle_shader_with(zbuffer, triangle, shader, cull_mode, &mut write_opaque);
     }
 
-    /// Draw a triangle defined by the vertices v0, v1, v2
-    /// filled with the diffuse texture
-    /// using a zbuffer to prevent drawing a hidden triangle over other
-    ///
-    /// zbuffer length must be image.width * image.height
-    /// and be filled with f64::NEG_INFINITY
-    pub fn triangle_zbuffer_texture(
+    /// Draw a transparent triangle like triangle_shader
+    /// but the fragments are blended with the pixels
+    /// and the zbuffer isn't changed
+    /// (the transparent triangles must be drawn after the opaque ones)
+    pub fn triangle_shader_blend<S: Shader<P>>(
         &mut self,
-        zbuffer: &mut Vec<f64>,
-        texture: &Self,
-        triangle: (Vector3, Vector3, Vector3),
-        texture_triangle: (Vector2, Vector2, Vector2),
-        intensity: f64,
-    ) {
-        let (v0, v1, v2) = triangle;
-        let w = self.width as usize;
-        let index = |i: usize, j: usize| i * (w as usize) + j;
-
-        // Convert vertices to points in the image
-        let p0 = v0.to_point();
-        let p1 = v1.to_point();
-        let p2 = v2.to_point();
-
-        // Find a box where that fit the triangle
-        let max_x = p0.x.max(p1.x).max(p2.x).min(self.width - 1);
-        let max_y = p0.y.max(p1.y).max(p2.y).min(self.height - 1);
-        let min_x = p0.x.min(p1.x).min(p2.x).max(0);
-        let min_y = p0.y.min(p1.y).min(p2.y).max(0);
-
-        // Draw the pixels in the box that are in the triangle
-        for x in min_x..=max_x {
-            for y in min_y..=max_y {
-                let p = Point { x, y };
-                let bary = Point::barycentric(p, (p0, p1, p2));
-                if inside_triangle_barycentric(bary) {
-                    let z = Vector3::lerp(bary, (v0, v1, v2)).unwrap().z;
-                    let i = index(y as usize, x as usize);
-                    if i < zbuffer.len() as usize && zbuffer[i] < z {
-                        zbuffer[i] = z;
-                        let t = Vector2::lerp(bary, texture_triangle)
-                            .unwrap()
-                            .to_texture_point(texture.width, texture.height);
-
-                        let color = texture.get(t).unwrap().light(intensity);
-                        self.set(p, color);
-                    }
-                }
-            }
-        }
+        zbuffer: &mut [f64],
+        triangle: (Vector4, Vector4, Vector4),
+        shader: &S,
+        cull_mode: CullMode,
+        mode: BlendMode,
+    ) where
+        P: Blend,
+    {
+        let mut write = |pixel: &mut P, _: &mut f64, _: usize, _: f64, color: P| {
+            *pixel = color.blend(*pixel, mode);
+        };
+        self.triangle_shader_with(zbuffer, triangle, shader, cull_mode, &mut write);
     }
 
-    /// Draw a triangle defined by the vertices v0, v1, v2
-    /// filled with color
-    /// using a zbuffer to prevent drawing a hidden triangle over other
-    /// and using Gourad shading
-    ///
-    /// zbuffer length must be image.width * image.height
-    /// and be filled with f64::NEG_INFINITY
-    pub fn triangle_zbuffer_gourad_color(
+    /// Draw a transparent triangle like triangle_shader
+    /// but the fragments are added to the order-independent transparency buffers
+    /// and the image and the zbuffer aren't changed
+    pub fn triangle_shader_oit<S: Shader<P>>(
         &mut self,
-        zbuffer: &mut Vec<f64>,
-        triangle: (Vector3, Vector3, Vector3),
-        triangle_normals: (Vector3, Vector3, Vector3),
-        color: Color,
-        light_source: Vector3,
-    ) {
-        let (v0, v1, v2) = triangle;
-        let w = self.width as usize;
-        let index = |i: usize, j: usize| i * (w as usize) + j;
-
-        // Convert vertices to points in the image
-        let p0 = v0.to_point();
-        let p1 = v1.to_point();
-        let p2 = v2.to_point();
-
-        // Find a box where that fit the triangle
-        let max_x = p0.x.max(p1.x).max(p2.x).min(self.width - 1);
-        let max_y = p0.y.max(p1.y).max(p2.y).min(self.height - 1);
-        let min_x = p0.x.min(p1.x).min(p2.x).max(0);
-        let min_y = p0.y.min(p1.y).min(p2.y).max(0);
-
-        // Draw the pixels in the box that are in the triangle
-        for x in min_x..=max_x {
-            for y in min_y..=max_y {
-                let p = Point { x, y };
-                let bary = Point::barycentric(p, (p0, p1, p2));
-                if inside_triangle_barycentric(bary) {
-                    let z = Vector3::lerp(bary, (v0, v1, v2)).unwrap().z;
-                    let i = index(y as usize, x as usize);
-                    if i < zbuffer.len() as usize && zbuffer[i] < z {
-                        zbuffer[i] = z;
-                        let normal = Vector3::lerp(bary, triangle_normals).unwrap();
-                        self.set(p, color.light(normal * light_source));
-                    }
-                }
-            }
-        }
+        zbuffer: &mut [f64],
+        oit: &mut OitBuffer,
+        triangle: (Vector4, Vector4, Vector4),
+        shader: &S,
+        cull_mode: CullMode,
+    ) where
+        P: Blend,
+    {
+        let mut write = |_: &mut P, _: &mut f64, i: usize, z: f64, color: P| {
+            oit.add(i, color.to_rgba(), z);
+        };
+        self.triangle_shader_with(zbuffer, triangle, shader, cull_mode, &mut write);
     }
 
-    /// Draw a triangle defined by the vertices v0, v1, v2
-    /// filled with texture
-    /// using a zbuffer to prevent drawing a hidden triangle over other
-    /// and using Gourad shading
-    ///
-    /// zbuffer length must be image.width * image.height
-    /// and be filled with f64::NEG_INFINITY
-    pub fn triangle_zbuffer_gourad_texture(
+    /// Clip and draw the triangle,
+    /// the fragments that pass the depth test are written by the function
+    /// write(sample, depth in the zbuffer, index of the sample, z, color)
+    fn triangle_shader_with<S, F>(
         &mut self,
-        zbuffer: &mut Vec<f64>,
-        texture: &Self,
-        triangle: (Vector3, Vector3, Vector3),
-        texture_triangle: (Vector2, Vector2, Vector2),
-        triangle_normals: (Vector3, Vector3, Vector3),
-        light_source: Vector3,
-    ) {
-        let (v0, v1, v2) = triangle;
-        let w = self.width as usize;
-        let index = |i: usize, j: usize| i * (w as usize) + j;
-
-        // Convert vertices to points in the image
-        let p0 = v0.to_point();
-        let p1 = v1.to_point();
-        let p2 = v2.to_point();
+        zbuffer: &mut [f64],
+        triangle: (Vector4, Vector4, Vector4),
+        shader: &S,
+        cull_mode: CullMode,
+        write: &mut F,
+    ) where
+        S: Shader<P>,
+        F: FnMut(&mut P, &mut f64, usize, f64, P),
+    {
+        let mut target = Target {
+            width: self.width,
+            height: self.height,
+            samples: self.samples,
+            rows: (0, self.height - 1),
+            pixels: &mut self.pixels,
+            zbuffer,
+        };
+        target.triangle(triangle, shader, cull_mode, write);
+    }
 
-        // Find a box where that fit the triangle
-        let max_x = p0.x.max(p1.x).max(p2.x).min(self.width - 1);
-        let max_y = p0.y.max(p1.y).max(p2.y).min(self.height - 1);
-        let min_x = p0.x.min(p1.x).min(p2.x).max(0);
-        let min_y = p0.y.min(p1.y).min(p2.y).max(0);
+    /// First and last rows of the image covered by the triangle,
+    /// or None if it's outside the image
+    fn triangle_rows(&self, triangle: (Vector4, Vector4, Vector4)) -> Option<(i32, i32)> {
+        let polygon = clip_triangle(triangle, self.width, self.height);
+        // The rows of the vertices like the rasterizer finds them
+        let rows = polygon.iter().map(|([_, y, _, w], _)| to_fixed(y / w));
+        let (first, last) = covered_pixels(
+            rows.clone().min()?,
+            rows.max()?,
+            extent(fixed_pattern(self.samples).map(|p| p.1)),
+        );
+        let first = first.max(0) as i32;
+        let last = last.min(self.height as i64 - 1) as i32;
+        (first <= last).then_some((first, last))
+    }
 
-        // Draw the pixels in the box that are in the triangle
-        for x in min_x..=max_x {
-            for y in min_y..=max_y {
-                let p = Point { x, y };
-                let bary = Point::barycentric(p, (p0, p1, p2));
-                if inside_triangle_barycentric(bary) {
-                    let z = Vector3::lerp(bary, (v0, v1, v2)).unwrap().z;
-                    let i = index(y as usize, x as usize);
-                    if i < zbuffer.len() as usize && zbuffer[i] < z {
-                        zbuffer[i] = z;
-
-                        let normal = Vector3::lerp(bary, triangle_normals).unwrap();
-                        let t = Vector2::lerp(bary, texture_triangle)
-                            .unwrap()
-                            .to_texture_point(texture.width, texture.height);
-
-                        let color = texture.get(t).unwrap().light(normal * light_source);
-                        self.set(p, color);
-                    }
-                }
-            }
-        }
+    /// Matrix that maps the clip coordinates
+    /// -w <= x, y, z <= w to the image and the zbuffer depth
+    /// like the rasterizer does
+    pub fn viewport(&self) -> Matrix4 {
+        let size = Vector3 {
+            x: self.width as f64,
+            y: self.height as f64,
+            z: DEPTH,
+        };
+        matrix_viewport(
+            Vector3 {
+                x: 0.0,
+                y: 0.0,
+                z: 0.0,
+            },
+            size,
+        )
     }
 
     /// Draw a line from (x0, y0) to (x1, y1)
-    pub fn line(
-        &mut self,
-        Point { x: x0, y: y0 }: Point,
-        Point { x: x1, y: y1 }: Point,
-        color: Color,
-    ) {
+    pub fn line(&mut self, Point { x: x0, y: y0 }: Point, Point { x: x1, y: y1 }: Point, color: P) {
         // This is my implementation of
         // Bresenham’s Line Drawing Algorithm
         // (or at least something close)
@@ -466,6 +1237,362 @@ impl Image {
             }
         }
     }
+}
+
+impl<P: Copy + Default + Send> Image<P> {
+    /// Draw the opaque faces in order like triangle_shader
+    /// with the shader of each face
+    ///
+    /// With more than one thread the faces are binned to the tiles
+    /// of the image that they cover, and the threads draw the tiles
+    /// running the vertex stage again with their copy of the shaders.
+    /// The result is identical to drawing the faces one by one
+    ///
+    /// zbuffer must have a depth for each sample of the image
+    pub fn faces_tiled<S>(
+        &mut self,
+        zbuffer: &mut [f64],
+        shaders: &[S],
+        faces: &[ShadedFace],
+        threads: usize,
+    ) where
+        S: Shader<P> + Clone + Send,
+    {
+        if threads <= 1 {
+            let mut shaders = shaders.to_vec();
+            for face in faces {
+                let triangle = face.vertex_stage(&mut shaders);
+                self.triangle_shader(zbuffer, triangle, &shaders[face.shader], face.cull_mode);
+            }
+            return;
+        }
+        assert_eq!(zbuffer.len(), self.pixels.len(), "Invalid zbuffer length");
+
+        // Faces that cover each tile
+        let tiles = ((self.height + TILE_ROWS - 1) / TILE_ROWS) as usize;
+        let mut bins: Vec<Vec<&ShadedFace>> = vec![Vec::new(); tiles];
+        let mut binning = shaders.to_vec();
+        for face in faces {
+            let triangle = face.vertex_stage(&mut binning);
+            if let Some((first, last)) = self.triangle_rows(triangle) {
+                for bin in &mut bins[(first / TILE_ROWS) as usize..=(last / TILE_ROWS) as usize] {
+                    bin.push(face);
+                }
+            }
+        }
+
+        // Each thread takes the next tile until all are drawn
+        let (width, height, samples) = (self.width, self.height, self.samples);
+        let size = width as usize * samples * TILE_ROWS as usize;
+        let tiles = Mutex::new(
+            self.pixels
+                .chunks_mut(size)
+                .zip(zbuffer.chunks_mut(size))
+                .zip(bins)
+                .enumerate(),
+        );
+
+        std::thread::scope(|scope| {
+            for _ in 0..threads {
+                let mut shaders = shaders.to_vec();
+                let tiles = &tiles;
+                scope.spawn(move || loop {
+                    let next = tiles.lock().unwrap().next();
+                    let (tile, ((pixels, zbuffer), bin)) = match next {
+                        Some(tile) => tile,
+                        None => break,
+                    };
+                    let first = tile as i32 * TILE_ROWS;
+                    let mut target = Target {
+                        width,
+                        height,
+                        samples,
+                        rows: (first, (first + TILE_ROWS - 1).min(height - 1)),
+                        pixels,
+                        zbuffer,
+                    };
+                    for face in bin {
+                        let triangle = face.vertex_stage(&mut shaders);
+                        let shader = &shaders[face.shader];
+                        target.triangle(triangle, shader, face.cull_mode, &mut write_opaque);
+                    }
+                });
+            }
+        });
+    }
+}
+
+impl<P: Blend> Image<P> {
+    /// Render with the anti-aliasing
+    ///
+    /// The function renders in an image bigger than this
+    /// or with many samples in each pixel,
+    /// and its image and its zbuffer are reduced to the size of this image.
+    /// The depth of a pixel is the depth of its closest sample
+    pub fn render_anti_aliased<F>(self, anti_aliasing: AntiAliasing, render: F) -> (Self, Vec<f64>)
+    where
+        F: FnOnce(Self) -> (Self, Vec<f64>),
+    {
+        let closest =
+            |depths: &mut dyn Iterator<Item = f64>| depths.fold(f64::NEG_INFINITY, f64::max);
+
+        match anti_aliasing {
+            AntiAliasing::None => render(self),
+            AntiAliasing::Supersampling(factor) => {
+                let factor = factor.max(1) as usize;
+                let (width, height) = (self.width as usize, self.height as usize);
+                let (image, zbuffer) = render(self.resolve().upsample(factor));
+
+                let big_width = width * factor;
+                let zbuffer = (0..width * height)
+                    .map(|i| {
+                        let (x, y) = ((i % width) * factor, (i / width) * factor);
+                        closest(
+                            &mut (0..factor * factor)
+                                .map(|k| zbuffer[(y + k / factor) * big_width + x + k % factor]),
+                        )
+                    })
+                    .collect();
+                (image.downsample(factor), zbuffer)
+            }
+            AntiAliasing::Multisampling(samples) => {
+                let samples = samples.count();
+                let (image, zbuffer) = render(self.to_multisample(samples));
+
+                let zbuffer = zbuffer
+                    .chunks(samples)
+                    .map(|depths| closest(&mut depths.iter().copied()))
+                    .collect();
+                (image.resolve(), zbuffer)
+            }
+        }
+    }
+
+    /// Draw a line from a to b with the style
+    ///
+    /// The pixels are blended with the color using the coverage of the line,
+    /// the pixel (x, y) is the square with the center (x, y) and the side 1
+    pub fn line_styled(&mut self, a: Vector2, b: Vector2, color: P, style: &LineStyle) {
+        let thin = style.width <= 1.0 && style.cap == LineCap::Butt && style.dash.is_empty();
+        if thin && !style.anti_aliased {
+            // Nearest pixel center, like the anti-aliased line
+            let to_point = |v: Vector2| Point {
+                x: v.x.round() as i32,
+                y: v.y.round() as i32,
+            };
+            self.line(to_point(a), to_point(b), color);
+            return;
+        }
+        if thin {
+            self.line_anti_aliased(a, b, color);
+            return;
+        }
+
+        if style.dash.is_empty() {
+            self.segment(a, b, color, style);
+            return;
+        }
+
+        let length = (b - a).norm();
+        let direction = (b - a).normalize();
+        let mut start = 0.0;
+        for (i, &dash) in style.dash.iter().cycle().enumerate() {
+            if start > length {
+                break;
+            }
+            // The even lengths are dashes and the odd lengths are gaps
+            if i % 2 == 0 {
+                let end = (start + dash).min(length);
+                self.segment(a + direction * start, a + direction * end, color, style);
+            }
+            start += dash;
+        }
+    }
+
+    /// Draw a line from a to b with one pixel of width
+    /// using Xiaolin Wu's algorithm
+    ///
+    /// Each step blends the two pixels closest to the line
+    /// with the color by their distance to the line
+    pub fn line_anti_aliased(&mut self, a: Vector2, b: Vector2, color: P) {
+        let fract = |v: f64| v - v.floor();
+
+        // Walk through the axis where the line is longer, from left to right
+        let steep = (b.y - a.y).abs() > (b.x - a.x).abs();
+        let swap = |v: Vector2| if steep { Vector2 { x: v.y, y: v.x } } else { v };
+        let (a, b) = (swap(a), swap(b));
+        let (a, b) = if a.x > b.x { (b, a) } else { (a, b) };
+
+        let dx = b.x - a.x;
+        let gradient = if dx == 0.0 { 1.0 } else { (b.y - a.y) / dx };
+        let mut plot = |x: f64, y: f64, coverage: f64| {
+            let (x, y) = if steep { (y, x) } else { (x, y) };
+            self.plot(
+                Point {
+                    x: x as i32,
+                    y: y as i32,
+                },
+                color,
+                coverage,
+            );
+        };
+
+        // The end points cover a part of their pixels
+        let mut end = |v: Vector2, gap: f64| {
+            let x = v.x.round();
+            let y = v.y + gradient * (x - v.x);
+            plot(x, y.floor(), (1.0 - fract(y)) * gap);
+            plot(x, y.floor() + 1.0, fract(y) * gap);
+            x
+        };
+        let x0 = end(a, 1.0 - fract(a.x + 0.5));
+        let x1 = end(b, fract(b.x + 0.5));
+
+        let mut y = a.y + gradient * (x0 - a.x);
+        for x in (x0 as i32 + 1)..(x1 as i32) {
+            y += gradient;
+            plot(x as f64, y.floor(), 1.0 - fract(y));
+            plot(x as f64, y.floor() + 1.0, fract(y));
+        }
+    }
+
+    /// Draw a segment of a thick line from a to b
+    fn segment(&mut self, a: Vector2, b: Vector2, color: P, style: &LineStyle) {
+        let half = style.width / 2.0;
+        let length = (b - a).norm();
+        // Any direction for a segment without length
+        let direction = if length > 0.0 {
+            (b - a) / length
+        } else {
+            Vector2 { x: 1.0, y: 0.0 }
+        };
+        let extension = match style.cap {
+            LineCap::Butt | LineCap::Round => 0.0,
+            LineCap::Square => half,
+        };
+
+        let margin = half + extension + 1.0;
+        let min_x = ((a.x.min(b.x) - margin).floor() as i32).max(0);
+        let min_y = ((a.y.min(b.y) - margin).floor() as i32).max(0);
+        let max_x = ((a.x.max(b.x) + margin).ceil() as i32).min(self.width - 1);
+        let max_y = ((a.y.max(b.y) + margin).ceil() as i32).min(self.height - 1);
+
+        for x in min_x..=max_x {
+            for y in min_y..=max_y {
+                let p = Vector2 {
+                    x: x as f64,
+                    y: y as f64,
+                } - a;
+                let along = p * direction;
+
+                // Distance from the pixel to the border of the line,
+                // negative inside the line
+                let distance = match style.cap {
+                    LineCap::Round => (p - direction * along.clamp(0.0, length)).norm() - half,
+                    LineCap::Butt | LineCap::Square => {
+                        let across = direction.cross(p).abs() - half;
+                        let outside = (-extension - along).max(along - length - extension);
+                        across.max(outside)
+                    }
+                };
+
+                let coverage = if style.anti_aliased {
+                    (0.5 - distance).clamp(0.0, 1.0)
+                } else if distance <= 0.0 {
+                    1.0
+                } else {
+                    0.0
+                };
+                self.plot(Point { x, y }, color, coverage);
+            }
+        }
+    }
+
+    /// Blend the color with the pixel using the coverage in [0, 1] as opacity
+    fn plot(&mut self, p: Point, color: P, coverage: f64) {
+        if coverage <= 0.0 {
+            return;
+        }
+        if let Some(pixel) = self.get(p) {
+            let [r, g, b, a] = color.to_rgba();
+            let color = P::from_rgba([r, g, b, a * coverage as f32]);
+            self.set(p, color.blend(pixel, BlendMode::Alpha));
+        }
+    }
+
+    /// Multisampled image where all samples of a pixel have its color
+    pub fn to_multisample(&self, samples: usize) -> Self {
+        let mut image = Self::multisample(self.width, self.height, samples);
+        // The first sample of each pixel
+        for (i, &color) in self.pixels.iter().step_by(self.samples).enumerate() {
+            image.pixels[i * samples..(i + 1) * samples].fill(color);
+        }
+        image
+    }
+
+    /// Average the samples of each pixel
+    pub fn resolve(self) -> Self {
+        if self.samples == 1 {
+            return self;
+        }
+        let pixels = self.pixels.chunks(self.samples).map(average).collect();
+        Self {
+            width: self.width,
+            height: self.height,
+            samples: 1,
+            pixels,
+        }
+    }
+
+    /// Image factor times bigger in each axis
+    /// where each pixel is a factor x factor block
+    ///
+    /// The image must have one sample in each pixel
+    fn upsample(&self, factor: usize) -> Self {
+        let (width, height) = (self.width as usize, self.height as usize);
+        let mut big = Self::new((width * factor) as i32, (height * factor) as i32);
+        for (i, pixel) in big.pixels.iter_mut().enumerate() {
+            let (x, y) = (i % (width * factor), i / (width * factor));
+            *pixel = self.pixels[(y / factor) * width + x / factor];
+        }
+        big
+    }
+
+    /// Image factor times smaller in each axis
+    /// where each pixel is the average of a factor x factor block
+    ///
+    /// The image must have one sample in each pixel
+    fn downsample(&self, factor: usize) -> Self {
+        let big_width = self.width as usize;
+        let (width, height) = (big_width / factor, self.height as usize / factor);
+        let mut small = Self::new(width as i32, height as i32);
+        for (i, pixel) in small.pixels.iter_mut().enumerate() {
+            let (x, y) = ((i % width) * factor, (i / width) * factor);
+            let block: Vec<P> = (0..factor * factor)
+                .map(|k| self.pixels[(y + k / factor) * big_width + x + k % factor])
+                .collect();
+            *pixel = average(&block);
+        }
+        small
+    }
+}
+
+/// Average of the colors
+fn average<P: Blend>(colors: &[P]) -> P {
+    let mut sum = [0.0; 4];
+    for color in colors {
+        for (s, c) in sum.iter_mut().zip(color.to_rgba()) {
+            *s += c;
+        }
+    }
+    P::from_rgba(sum.map(|s| s / colors.len() as f32))
+}
+
+impl Image {
+    /// Test if any pixel isn't opaque
+    pub fn is_transparent(&self) -> bool {
+        self.pixels.iter().any(|color| color.a < 255)
+    }
 
     /// Save the image as a bitmap
     #[allow(dead_code)]
@@ -527,8 +1654,9 @@ impl Image {
         file.write_all(&0u32.to_le_bytes())?; // 4 bytes
 
         // * Image
+        // 24 bits without the alpha
         for &color in &self.pixels {
-            file.write_all(&color.to_bytes())?;
+            file.write_all(&color.to_bytes()[..3])?;
         }
 
         file.flush()?;
@@ -536,8 +1664,13 @@ impl Image {
     }
 
     /// Save the image as a Truevision TGA file
-    pub fn save_tga(&self, path: &str, rle: bool) -> std::io::Result<()> {
+    ///
+    /// The pixels have 32 bits with the alpha channel
+    /// or 24 bits without it
+    pub fn save_tga(&self, path: &str, rle: bool, alpha: bool) -> std::io::Result<()> {
         let mut file = BufWriter::new(File::create(path)?);
+        // Bytes per pixel
+        let depth = if alpha { 4 } else { 3 };
 
         // * Header
         // ID length
@@ -571,10 +1704,13 @@ impl Image {
         file.write_all(&(self.height as u16).to_le_bytes())?; // 2 bytes
 
         // Pixel depth
-        file.write_all(&24u8.to_le_bytes())?; // 1 bytes
+        file.write_all(&(8 * depth as u8).to_le_bytes())?; // 1 bytes
 
-        // Image descriptor (0 works fine)
-        file.write_all(&0u8.to_le_bytes())?; // 1 byte
+        // Image descriptor
+        // the bits 0-3 are the number of alpha bits in a pixel
+        // and the origin is the bottom left corner
+        let alpha_bits = if alpha { 8u8 } else { 0u8 };
+        file.write_all(&alpha_bits.to_le_bytes())?; // 1 byte
 
         // * Image and color map data
         // Image ID (we set to 0)
@@ -610,13 +1746,13 @@ impl Image {
                             while raw.len() - 1 > (MAX_COUNT as usize) {
                                 file.write_all(&MAX_COUNT.to_le_bytes())?;
                                 for _ in 0..MAX_COUNT + 1 {
-                                    file.write_all(&raw.pop_front().unwrap().to_bytes())?;
+                                    file.write_all(&raw.pop_front().unwrap().to_bytes()[..depth])?;
                                 }
                             }
                             let len = (raw.len() as u8) - 1;
                             file.write_all(&len.to_le_bytes())?;
                             for &color in &raw {
-                                file.write_all(&color.to_bytes())?;
+                                file.write_all(&color.to_bytes()[..depth])?;
                             }
                             raw.clear();
                         }
@@ -624,12 +1760,12 @@ impl Image {
                         // Write rle packet
                         while count - 1 > (MAX_COUNT as u32) {
                             file.write_all(&(MAX_COUNT | RLE).to_le_bytes())?;
-                            file.write_all(&color.to_bytes())?;
+                            file.write_all(&color.to_bytes()[..depth])?;
                             count -= (MAX_COUNT as u32) + 1;
                         }
                         let count = ((count as u8) - 1) | RLE;
                         file.write_all(&count.to_le_bytes())?;
-                        file.write_all(&color.to_bytes())?;
+                        file.write_all(&color.to_bytes()[..depth])?;
                     }
                 }
                 if !raw.is_empty() {
@@ -637,20 +1773,20 @@ impl Image {
                     while raw.len() - 1 > (MAX_COUNT as usize) {
                         file.write_all(&MAX_COUNT.to_le_bytes())?;
                         for _ in 0..MAX_COUNT + 1 {
-                            file.write_all(&raw.pop_front().unwrap().to_bytes())?;
+                            file.write_all(&raw.pop_front().unwrap().to_bytes()[..depth])?;
                         }
                     }
                     let len = (raw.len() as u8) - 1;
                     file.write_all(&len.to_le_bytes())?;
                     for &color in &raw {
-                        file.write_all(&color.to_bytes())?;
+                        file.write_all(&color.to_bytes()[..depth])?;
                     }
                     raw.clear();
                 }
             }
         } else {
-            for &Color { r, g, b } in &self.pixels {
-                file.write_all(&[r, g, b])?;
+            for &color in &self.pixels {
+                file.write_all(&color.to_bytes()[..depth])?;
             }
         }
 
@@ -668,32 +1804,48 @@ impl Image {
         Ok(())
     }
 
-    // Load the image from a True Vision TGA fil
-    pub fn load_tga(path: &str) -> std::io::Result<Self> {
-        let mut file = BufReader::new(File::open(path)?);
+    /// Load the image from a True Vision TGA file
+    ///
+    /// The true color images can have 24 or 32 bits (with alpha) pixels
+    /// and the grayscale images 8 bits pixels, all of them are converted to RGBA
+    pub fn load_tga(path: &str) -> Result<Self, LoadError> {
+        let io = |error: std::io::Error| LoadError::io(path, error);
+        let invalid = |reason: String| LoadError::invalid(path, None, reason);
+
+        let mut file = BufReader::new(File::open(path).map_err(io)?);
         let mut buffer = [0u8; 4];
 
         // * Header
         // ID length
-        file.read_exact(&mut buffer[..1])?;
+        file.read_exact(&mut buffer[..1]).map_err(io)?;
         let image_id_length = u8::from_le_bytes(buffer[..1].try_into().unwrap()); // 1 byte
 
         // Color map type
-        file.read_exact(&mut buffer[..1])?;
+        file.read_exact(&mut buffer[..1]).map_err(io)?;
         let color_map = u8::from_le_bytes(buffer[..1].try_into().unwrap()); // 1 byte
-        assert_eq!(
-            color_map, 0,
-            "Load TGA: Color map {} not implemented!",
-            color_map
-        );
+        if color_map != 0 {
+            return Err(invalid(format!(
+                "Load TGA: Color map {} not implemented!",
+                color_map
+            )));
+        }
 
         // Image type (Compression and color types)
-        file.read_exact(&mut buffer[..1])?;
+        file.read_exact(&mut buffer[..1]).map_err(io)?;
         let image_type = u8::from_le_bytes(buffer[..1].try_into().unwrap()); // 1 byte
-        let rle = match image_type {
-            2u8 => false,
-            10u8 => true,
-            _ => panic!("Load TGA: Image type {} not implemented!", image_type),
+
+        // (grayscale, rle)
+        let (grayscale, rle) = match image_type {
+            2u8 => (false, false),
+            3u8 => (true, false),
+            10u8 => (false, true),
+            11u8 => (true, true),
+            _ => {
+                return Err(invalid(format!(
+                    "Load TGA: Image type {} not implemented!",
+                    image_type
+                )))
+            }
         };
 
         // ** Color Map specification
@@ -701,76 +1853,120 @@ impl Image {
         // First entry index // 2 bytes
         // Color map length // 2 bytes
         // Color map entry size // 1 byte
-        file.seek(SeekFrom::Current(5))?;
+        file.seek(SeekFrom::Current(5)).map_err(io)?;
 
         // ** Image specification
         // X and Y origin
-        file.read_exact(&mut buffer[..4])?;
+        file.read_exact(&mut buffer[..4]).map_err(io)?;
         let x_origin = u16::from_le_bytes(buffer[..2].try_into().unwrap()); // 2 bytes
         let y_origin = u16::from_le_bytes(buffer[2..4].try_into().unwrap()); // 2 bytes
         if x_origin != 0 || y_origin != 0 {
-            panic!("Load TGA: Only origin (0,0) is implemented!");
+            return Err(invalid(
+                "Load TGA: Only origin (0,0) is implemented!".to_string(),
+            ));
         }
 
         // Image width and height
-        file.read_exact(&mut buffer[..4])?;
+        file.read_exact(&mut buffer[..4]).map_err(io)?;
         let width = u16::from_le_bytes(buffer[..2].try_into().unwrap()); // 2 bytes
         let height = u16::from_le_bytes(buffer[2..4].try_into().unwrap()); // 2 bytes
+        let size = (width as usize) * (height as usize);
+        if size == 0 {
+            return Err(invalid(format!(
+                "Load TGA: Empty image {}x{}",
+                width, height
+            )));
+        }
 
         // Pixel depth
-        file.read_exact(&mut buffer[..1])?;
+        file.read_exact(&mut buffer[..1]).map_err(io)?;
         let color_depth = u8::from_le_bytes(buffer[..1].try_into().unwrap()); // 1 bytes
-        assert_eq!(
-            color_depth, 24,
-            "Load TGA: Color depth {} not implemented!",
-            color_depth
-        );
+        let depth = match (grayscale, color_depth) {
+            (true, 8) => 1,
+            (false, 24) => 3,
+            (false, 32) => 4,
+            _ => {
+                return Err(invalid(format!(
+                    "Load TGA: Color depth {} not implemented!",
+                    color_depth
+                )))
+            }
+        };
 
-        // Image descriptor (0 works fine)
-        file.read_exact(&mut buffer[..1])?;
+        // Image descriptor
+        // bits 0-3: number of alpha bits of the pixels (8 or 0 if not used)
+        // bit 4: the pixels go from right to left
+        // bit 5: the pixels go from top to bottom
+        // bits 6-7: interleaving (must be 0)
+        file.read_exact(&mut buffer[..1]).map_err(io)?;
         let descriptor = u8::from_le_bytes(buffer[..1].try_into().unwrap()); // 1 bytes
-        assert_eq!(
-            descriptor & 0b1100111,
-            0u8,
-            "Load TGA: Image Descriptor {} not implemented!",
-            descriptor
-        );
-        let flip_horizontally = (descriptor & 0b0001000) != 0;
-        let flip_vertically = (descriptor & 0b0010000) != 0;
+        if descriptor & 0b11000000 != 0 {
+            return Err(invalid(format!(
+                "Load TGA: Image Descriptor {} not implemented!",
+                descriptor
+            )));
+        }
+        let alpha = match (depth, descriptor & 0b00001111) {
+            (_, 0) => false,
+            (4, 8) => true,
+            (_, alpha_bits) => {
+                return Err(invalid(format!(
+                    "Load TGA: {} alpha bits not implemented for color depth {}!",
+                    alpha_bits, color_depth
+                )))
+            }
+        };
+        let flip_horizontally = (descriptor & 0b00010000) != 0;
+        let flip_vertically = (descriptor & 0b00100000) != 0;
+
+        // Read a pixel of depth bytes
+        let mut read_color = |file: &mut BufReader<File>| -> Result<Color, LoadError> {
+            file.read_exact(&mut buffer[..depth]).map_err(io)?;
+            Ok(match depth {
+                1 => Color::gray(buffer[0]),
+                4 if alpha => Color::from_bytes(buffer),
+                _ => Color::from_bytes([buffer[0], buffer[1], buffer[2], 255]),
+            })
+        };
 
         // * Image and color map data
         // Image ID
-        file.seek(SeekFrom::Current(image_id_length as i64))?;
-        // Color Map (we assert to no color map)
+        file.seek(SeekFrom::Current(image_id_length as i64))
+            .map_err(io)?;
+        // Color Map (there is no color map)
         // Image Data
         let mut image = Self::new(width as i32, height as i32);
         image.pixels.clear();
         if rle {
-            while image.pixels.len() < (width as usize) * (height as usize) {
-                file.read_exact(&mut buffer[..1])?;
-                let packet_size = u8::from_le_bytes(buffer[..1].try_into().unwrap());
+            while image.pixels.len() < size {
+                let mut packet = [0u8; 1];
+                file.read_exact(&mut packet).map_err(io)?;
+                let packet_size = u8::from_le_bytes(packet);
 
                 if packet_size & 0b10000000 == 0 {
                     // Raw packet
                     for _ in 0..=packet_size {
-                        file.read_exact(&mut buffer[..3])?;
-                        let color = Color::from_bytes(buffer[..3].try_into().unwrap());
+                        let color = read_color(&mut file)?;
                         image.pixels.push(color)
                     }
                 } else {
                     // RLE packet
                     let packet_size = packet_size & 0b01111111;
-                    file.read_exact(&mut buffer[..3])?;
-                    let color = Color::from_bytes(buffer[..3].try_into().unwrap());
+                    let color = read_color(&mut file)?;
                     for _ in 0..=packet_size {
                         image.pixels.push(color);
                     }
                 }
             }
+
+            if image.pixels.len() > size {
+                return Err(invalid(
+                    "Load TGA: RLE packet goes beyond the end of the image".to_string(),
+                ));
+            }
         } else {
-            for _ in 0..(width * height) {
-                file.read_exact(&mut buffer[..3])?;
-                let color = Color::from_bytes(buffer[..3].try_into().unwrap());
+            for _ in 0..size {
+                let color = read_color(&mut file)?;
                 image.pixels.push(color);
             }
         }
@@ -786,3 +1982,274 @@ impl Image {
         Ok(image)
     }
 }
+
+impl HdrImage {
+    /// Convert the image to 8-bit colors
+    ///
+    /// The colors are multiplied by the exposure,
+    /// compressed by the tone mapping
+    /// and encoded with the gamma (c^(1 / gamma))
+    pub fn tone_map(&self, tone_mapping: ToneMapping, exposure: f32, gamma: f32) -> Image {
+        let pixels = self
+            .pixels
+            .iter()
+            .map(|&color| {
+                (color * exposure)
+                    .map(|c| {
+                        tone_mapping
+                            .apply(c.max(0.0))
+                            .clamp(0.0, 1.0)
+                            .powf(1.0 / gamma)
+                    })
+                    .to_color()
+            })
+            .collect();
+        Image {
+            width: self.width,
+            height: self.height,
+            samples: self.samples,
+            pixels,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::Vertex;
+    use crate::shader::DepthShader;
+
+    /// Vertex with the position (x, y) in an image of the size and the depth z
+    fn vertex((x, y, z): (f64, f64, f64), size: i32) -> Vertex {
+        let zero = Vector3 {
+            x: 0.0,
+            y: 0.0,
+            z: 0.0,
+        };
+        Vertex {
+            // Clip coordinates with w = 1
+            position: Vector3 {
+                x: x * 2.0 / size as f64 - 1.0,
+                y: y * 2.0 / size as f64 - 1.0,
+                z,
+            },
+            texture: None,
+            normal: zero,
+            tangent: None,
+            material: 0,
+        }
+    }
+
+    /// Draw the triangles with 1 and 4 threads
+    /// and test that the images and the zbuffers are identical
+    fn assert_tiled_identical(triangles: &[[(f64, f64, f64); 3]], size: i32, samples: usize) {
+        let shaders = [DepthShader::new(Matrix4::identity())];
+        let faces: Vec<ShadedFace> = triangles
+            .iter()
+            .map(|triangle| ShadedFace {
+                shader: 0,
+                vertices: triangle.iter().map(|&p| vertex(p, size)).collect(),
+                cull_mode: CullMode {
+                    cull: Cull::None,
+                    front: Winding::CounterClockwise,
+                },
+            })
+            .collect();
+
+        let draw = |threads: usize| {
+            let mut image = Image::multisample(size, size, samples);
+            let mut zbuffer = image.zbuffer();
+            image.faces_tiled(&mut zbuffer, &shaders, &faces, threads);
+            (image.pixels, zbuffer)
+        };
+        let (serial_pixels, serial_zbuffer) = draw(1);
+        let (tiled_pixels, tiled_zbuffer) = draw(4);
+        assert!(serial_pixels == tiled_pixels, "The images are different");
+        assert!(
+            serial_zbuffer == tiled_zbuffer,
+            "The zbuffers are different"
+        );
+    }
+
+    #[test]
+    fn tiled_edge_rounded_to_next_tile() {
+        // The horizontal edges are rounded to the row 16, in the second tile
+        let triangles = [
+            [(2.0, 15.999, 0.5), (30.0, 15.999, 0.5), (16.0, 2.0, 0.5)],
+            [(34.0, 2.0, 0.5), (62.0, 2.0, 0.5), (48.0, 15.999, 0.5)],
+            [(2.0, 31.999, 0.5), (16.0, 18.0, 0.5), (30.0, 31.999, 0.5)],
+        ];
+        for samples in [1, 4] {
+            assert_tiled_identical(&triangles, 64, samples);
+        }
+    }
+
+    #[test]
+    fn tiled_identical_to_serial() {
+        // Small triangles that cross the tiles,
+        // from a linear congruential generator
+        let mut seed: u64 = 1;
+        let mut random = |max: f64| {
+            seed = seed.wrapping_mul(6364136223846793005).wrapping_add(1);
+            (seed >> 11) as f64 / (1u64 << 53) as f64 * max
+        };
+        // The rows of the vertices are a bit less than an integer
+        // to have horizontal edges rounded to the next row
+        let triangles: Vec<[(f64, f64, f64); 3]> = (0..400)
+            .map(|_| {
+                let (x, y) = (random(272.0) - 8.0, random(272.0).floor() - 8.001);
+                let size = random(16.0);
+                [0; 3].map(|_| {
+                    (
+                        x + random(size),
+                        y + random(size).floor(),
+                        random(2.0) - 1.0,
+                    )
+                })
+            })
+            .collect();
+        for samples in [1, 2, 4, 8, 16] {
+            assert_tiled_identical(&triangles, 256, samples);
+        }
+    }
+
+    #[test]
+    fn fan_covers_each_sample_once() {
+        // A convex polygon split in 9 triangles around the center,
+        // the vertices are in 1/16 of pixel like the samples
+        // to have samples exactly on the edges
+        let size = 64;
+        let center = (32.0, 32.0);
+        let rim: Vec<(f64, f64)> = [0.0, 35.0, 80.0, 90.0, 135.0, 170.0, 225.0, 270.0, 310.0]
+            .iter()
+            .map(|&angle: &f64| {
+                let (sin, cos) = angle.to_radians().sin_cos();
+                let snap = |c: f64| (c * 16.0).round() / 16.0;
+                (snap(center.0 + 24.0 * cos), snap(center.1 + 24.0 * sin))
+            })
+            .collect();
+        let clip = |(x, y): (f64, f64)| Vector4 {
+            x: x * 2.0 / size as f64 - 1.0,
+            y: y * 2.0 / size as f64 - 1.0,
+            z: 0.5,
+            w: 1.0,
+        };
+        // Side of the point from the edge from a to b
+        let side = |a: (f64, f64), b: (f64, f64), p: (f64, f64)| {
+            (b.0 - a.0) * (p.1 - a.1) - (b.1 - a.1) * (p.0 - a.0)
+        };
+
+        let shader = DepthShader::new(Matrix4::identity());
+        let cull_mode = CullMode {
+            cull: Cull::None,
+            front: Winding::CounterClockwise,
+        };
+        for samples in [1, 4, 8] {
+            // Count the writes of each sample in the red channel
+            let mut image: Image = Image::multisample(size, size, samples);
+            let mut zbuffer = image.zbuffer();
+            for i in 0..rim.len() {
+                let (a, b) = (rim[i], rim[(i + 1) % rim.len()]);
+                let triangle = (clip(center), clip(a), clip(b));
+                image.triangle_shader_with(
+                    &mut zbuffer,
+                    triangle,
+                    &shader,
+                    cull_mode,
+                    &mut |pixel: &mut Color, _: &mut f64, _, _, _| pixel.r += 1,
+                );
+            }
+
+            let pattern = sample_pattern(samples);
+            for (i, pixel) in image.pixels.iter().enumerate() {
+                let (x, y) = ((i / samples) as i32 % size, (i / samples) as i32 / size);
+                let (dx, dy) = pattern[i % samples];
+                let p = (x as f64 + dx, y as f64 + dy);
+                let sides: Vec<f64> = (0..rim.len())
+                    .map(|j| side(rim[j], rim[(j + 1) % rim.len()], p))
+                    .collect();
+                assert!(pixel.r <= 1, "Sample {:?} written {} times", p, pixel.r);
+                if sides.iter().all(|&s| s > 0.0) {
+                    assert_eq!(pixel.r, 1, "Gap at the sample {:?}", p);
+                } else if sides.iter().any(|&s| s < 0.0) {
+                    assert_eq!(pixel.r, 0, "Sample {:?} outside written", p);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn cull_mode_by_winding() {
+        let cases = [
+            (Cull::None, Winding::CounterClockwise, [false, false]),
+            (Cull::None, Winding::Clockwise, [false, false]),
+            (Cull::Back, Winding::CounterClockwise, [false, true]),
+            (Cull::Back, Winding::Clockwise, [true, false]),
+            (Cull::Front, Winding::CounterClockwise, [true, false]),
+            (Cull::Front, Winding::Clockwise, [false, true]),
+        ];
+        for (cull, front, [positive, negative]) in cases {
+            let cull_mode = CullMode { cull, front };
+            assert_eq!(cull_mode.is_culled(12), positive, "{:?}", cull_mode);
+            assert_eq!(cull_mode.is_culled(-12), negative, "{:?}", cull_mode);
+            // Degenerate triangles are never culled
+            assert!(!cull_mode.is_culled(0), "{:?}", cull_mode);
+        }
+    }
+
+    #[test]
+    fn load_empty_tga() {
+        // Header of an uncompressed 0x4 image with 24 bits
+        let mut header = [0u8; 18];
+        header[2] = 2;
+        header[14] = 4;
+        header[16] = 24;
+        let path = std::env::temp_dir().join("tinyrenderer_empty.tga");
+        std::fs::write(&path, header).unwrap();
+        let result = Image::load_tga(path.to_str().unwrap());
+        std::fs::remove_file(&path).unwrap();
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn load_tga_without_alpha_bits() {
+        // Header of an uncompressed 1x1 image with 32 bits and 0 alpha bits
+        let mut header = vec![0u8; 18];
+        header[2] = 2;
+        header[12] = 1;
+        header[14] = 1;
+        header[16] = 32;
+        let path = std::env::temp_dir().join("tinyrenderer_no_alpha.tga");
+
+        let mut file = header.clone();
+        file.extend([10, 20, 30, 0]);
+        std::fs::write(&path, &file).unwrap();
+        let image = Image::load_tga(path.to_str().unwrap()).unwrap();
+        let color = image.get(Point { x: 0, y: 0 }).unwrap();
+        assert_eq!(
+            color,
+            Color {
+                r: 30,
+                g: 20,
+                b: 10,
+                a: 255
+            }
+        );
+
+        // 8 alpha bits keep the alpha of the pixel
+        header[17] = 8;
+        let mut file = header.clone();
+        file.extend([10, 20, 30, 0]);
+        std::fs::write(&path, &file).unwrap();
+        let image = Image::load_tga(path.to_str().unwrap()).unwrap();
+        assert_eq!(image.get(Point { x: 0, y: 0 }).unwrap().a, 0);
+
+        // Any other number of alpha bits is rejected
+        header[17] = 1;
+        file[..18].copy_from_slice(&header);
+        std::fs::write(&path, &file).unwrap();
+        let result = Image::load_tga(path.to_str().unwrap());
+        std::fs::remove_file(&path).unwrap();
+        assert!(result.is_err());
+    }
+}
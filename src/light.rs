@@ -0,0 +1,140 @@
+use crate::modules::{Color, HdrColor, Vector3};
+
+/// A light source
+///
+/// The intensity multiplies the color of the light
+/// and the attenuation (constant, linear, quadratic) divides the intensity
+/// by constant + linear * d + quadratic * d² at the distance d of the light
+#[derive(Clone, Copy, Debug)]
+pub enum Light {
+    /// A light infinitely far in the direction (like the sun),
+    /// the direction goes from the surface to the light
+    Directional {
+        direction: Vector3,
+        color: Color,
+        intensity: f64,
+    },
+    /// A light in the position that emits in every direction
+    Point {
+        position: Vector3,
+        color: Color,
+        intensity: f64,
+        attenuation: (f64, f64, f64),
+    },
+    /// A light in the position that emits in a cone around the direction
+    ///
+    /// The angle is the half-angle of the cone in radians
+    /// and the light fades to zero in the last falloff radians of the cone
+    Spot {
+        position: Vector3,
+        direction: Vector3,
+        color: Color,
+        intensity: f64,
+        attenuation: (f64, f64, f64),
+        angle: f64,
+        falloff: f64,
+    },
+}
+
+impl Light {
+    /// White directional light with intensity 1
+    pub fn directional(direction: Vector3) -> Self {
+        Self::Directional {
+            direction,
+            color: Color::gray(255),
+            intensity: 1.0,
+        }
+    }
+
+    pub fn color(&self) -> Color {
+        match *self {
+            Self::Directional { color, .. }
+            | Self::Point { color, .. }
+            | Self::Spot { color, .. } => color,
+        }
+    }
+
+    /// Direction from the point to the light (normalized)
+    /// and the intensity of the light that reaches the point
+    pub fn incidence(&self, point: Vector3) -> (Vector3, f64) {
+        /// Direction to the position and intensity after the attenuation
+        fn attenuate(
+            point: Vector3,
+            position: Vector3,
+            intensity: f64,
+            (constant, linear, quadratic): (f64, f64, f64),
+        ) -> (Vector3, f64) {
+            let d = (position - point).norm();
+            let attenuation = constant + linear * d + quadratic * d * d;
+            (
+                (position - point).normalize(),
+                intensity / attenuation.max(f64::EPSILON),
+            )
+        }
+
+        match *self {
+            Self::Directional {
+                direction,
+                intensity,
+                ..
+            } => (direction.normalize(), intensity),
+            Self::Point {
+                position,
+                intensity,
+                attenuation,
+                ..
+            } => attenuate(point, position, intensity, attenuation),
+            Self::Spot {
+                position,
+                direction,
+                intensity,
+                attenuation,
+                angle,
+                falloff,
+                ..
+            } => {
+                let (l, intensity) = attenuate(point, position, intensity, attenuation);
+                // Angle between the axis of the cone and the ray to the point
+                let theta = (-(l * direction.normalize())).clamp(-1.0, 1.0).acos();
+                let cone = if theta >= angle {
+                    0.0
+                } else if theta <= angle - falloff {
+                    1.0
+                } else {
+                    (angle - theta) / falloff
+                };
+                (l, intensity * cone)
+            }
+        }
+    }
+}
+
+/// Diffuse intensity of each light in the point
+/// of a surface with the normal (Lambert's cosine law)
+pub fn intensities(lights: &[Light], point: Vector3, normal: Vector3) -> Vec<f64> {
+    lights
+        .iter()
+        .map(|light| {
+            let (l, intensity) = light.incidence(point);
+            intensity * (normal * l)
+        })
+        .collect()
+}
+
+/// Sum of the color lit by each light with its intensity
+///
+/// The lights are summed with floating point colors
+/// and the sum is clamped, the alpha of the color is kept
+pub fn shade(color: Color, lights: &[Light], intensities: impl Iterator<Item = f64>) -> Color {
+    let alpha = color.a;
+    let color = HdrColor::from(color);
+    lights
+        .iter()
+        .zip(intensities)
+        .fold(HdrColor::gray(0.0), |sum, (light, intensity)| {
+            let intensity = (intensity as f32).max(0.0);
+            sum + color * HdrColor::from(light.color()) * intensity
+        })
+        .to_color()
+        .with_alpha(alpha)
+}
@@ -1,12 +1,42 @@
 mod modules;
-use crate::modules::{Color, Point, Vector3};
+use crate::modules::{Color, Matrix4, Point, Vector3, GAMMA};
+
+mod camera;
+use crate::camera::Camera;
+
+mod error;
 
 mod image;
-use crate::image::Image;
+use crate::image::{
+    AntiAliasing, Cull, CullMode, HdrImage, Image, LineCap, LineStyle, Samples, ToneMapping,
+};
+
+mod light;
+use crate::light::Light;
+
+mod material;
 
 mod model;
 use crate::model::Model;
 
+mod scene;
+use crate::scene::Scene;
+
+mod shader;
+
+mod shadow;
+use crate::shadow::ShadowMap;
+
+mod simd;
+
+mod ssao;
+
+mod texture;
+use crate::texture::{Filter, Sampler, Wrap};
+
+mod transparency;
+use crate::transparency::Transparency;
+
 // const MODEL: &str = "diablo3_pose";
 const MODEL: &str = "african_head";
 // const MODEL: &str = "african_head_novn";
@@ -41,23 +71,26 @@ const UP: Vector3 = Vector3 {
     z: 0.0,
 };
 
-const VIEWPORT: (Vector3, Vector3) = (
-    Vector3 {
-        x: 0.0,
-        y: 0.0,
-        z: 0.0,
-    },
-    Vector3 {
-        x: WIDTH as f64,
-        y: HEIGHT as f64,
-        z: 255.0,
-    },
-);
+// Vertical field of view in degrees
+// that shows [-1, 1] in the center at the distance 3 of the camera
+const FOV: f64 = 36.87;
+
+const NEAR: f64 = 1.5;
+const FAR: f64 = 6.0;
 
 /// A function to reduce repeated code
 ///
 /// Save the rendered image to a file and print some things
 fn wrap_render<F>(title: &str, path: &str, render: F)
+where
+    F: FnOnce(Image) -> Image,
+{
+    wrap_render_alpha(title, path, false, render)
+}
+
+/// Like wrap_render but the image can be saved with the alpha channel,
+/// where nothing is rendered the image is transparent
+fn wrap_render_alpha<F>(title: &str, path: &str, alpha: bool, render: F)
 where
     F: FnOnce(Image) -> Image,
 {
@@ -67,7 +100,9 @@ where
     let image = render(Image::new(WIDTH, HEIGHT));
 
     println!("> Saving");
-    image.save_tga(path, true).expect("Can't save the image");
+    image
+        .save_tga(path, true, alpha)
+        .expect("Can't save the image");
 
     println!();
 }
@@ -75,15 +110,37 @@ where
 fn main() {
     println!("Opening model and texture\n");
 
-    let model = {
+    let mut model = {
         let image_path_string = format!("obj/{0}/{0}.obj", MODEL);
         let texture_path_string = format!("obj/{0}/{0}_diffuse.tga", MODEL);
 
         let image_path = image_path_string.as_str();
         let texture_path = texture_path_string.as_str();
 
-        Model::new(image_path, Some(texture_path)).expect("Can't open model")
+        Model::new(image_path, Some(texture_path))
+            .unwrap_or_else(|error| panic!("Can't open model: {}", error))
     };
+    // The normal map found next to the texture is used after the first renders
+    let normal_map = model.take_normal_map();
+    println!("Objects: {:?}", model.objects());
+    println!("Groups: {:?}\n", model.groups());
+
+    let aspect = (WIDTH as f64) / (HEIGHT as f64);
+    let lights = [Light::directional(LIGHT_SOURCE)];
+
+    // Camera in front of the model
+    let front = Vector3 {
+        x: 0.0,
+        y: 0.0,
+        z: CAMERA.z,
+    };
+    // The model in [-1, 1] fills the image
+    let orthographic = Camera::orthographic(2.0, aspect, CAMERA.z - 1.0, CAMERA.z + 1.0)
+        .look_at(front, CENTER, UP);
+    let perspective =
+        Camera::perspective(FOV.to_radians(), aspect, NEAR, FAR).look_at(front, CENTER, UP);
+    let camera =
+        Camera::perspective(FOV.to_radians(), aspect, NEAR, FAR).look_at(CAMERA, CENTER, UP);
 
     let mut zbuffer: Vec<f64> = Vec::new();
     let index = |i: usize| Point {
@@ -92,15 +149,20 @@ fn main() {
     };
 
     wrap_render("Wireframe", "wireframe.tga", |image| {
-        model.render_wireframe(image, COLOR)
+        model.render_wireframe(image, &orthographic, COLOR, &LineStyle::default())
+    });
+
+    wrap_render("Wireframe - Anti-aliased", "wireframe_aa.tga", |image| {
+        let style = LineStyle::default().with_anti_aliasing(true);
+        model.render_wireframe(image, &orthographic, COLOR, &style)
     });
 
     wrap_render("Triangles", "triangles.tga", |image| {
-        model.render_triangles(image, COLOR, LIGHT_SOURCE)
+        model.render_triangles(image, &orthographic, COLOR, &lights)
     });
 
     wrap_render("Render Color", "color.tga", |image| {
-        let res = model.render_color(image, VIEWPORT, COLOR, LIGHT_SOURCE);
+        let res = model.render_color(image, &orthographic, COLOR, &lights);
         zbuffer = res.1;
         res.0
     });
@@ -116,19 +178,19 @@ fn main() {
     );
 
     wrap_render("Render Color", "color.tga", |image| {
-        let res = model.render_color(image, VIEWPORT, COLOR, LIGHT_SOURCE);
+        let res = model.render_color(image, &orthographic, COLOR, &lights);
         zbuffer = res.1;
         res.0
     });
 
     wrap_render("Render Texture", "texture.tga", |image| {
-        let res = model.render_texture(image, VIEWPORT, LIGHT_SOURCE);
+        let res = model.render_texture(image, &orthographic, &lights);
         zbuffer = res.1;
         res.0
     });
 
     wrap_render("Perspective", "perspective.tga", |image| {
-        let res = model.render_perspective(image, VIEWPORT, CAMERA.z, LIGHT_SOURCE);
+        let res = model.render_texture(image, &perspective, &lights);
         zbuffer = res.1;
         res.0
     });
@@ -144,13 +206,13 @@ fn main() {
     );
 
     wrap_render("Gouraud Color", "gouraud_color.tga", |image| {
-        let res = model.render_gouraud_color(image, VIEWPORT, COLOR, LIGHT_SOURCE);
+        let res = model.render_gouraud_color(image, &orthographic, COLOR, &lights);
         zbuffer = res.1;
         res.0
     });
 
     wrap_render("Gouraud", "gouraud.tga", |image| {
-        let res = model.render_gouraud(image, VIEWPORT, CAMERA.z, LIGHT_SOURCE);
+        let res = model.render_gouraud(image, &perspective, &lights);
         zbuffer = res.1;
         res.0
     });
@@ -162,7 +224,7 @@ fn main() {
     });
 
     wrap_render("Look at", "look.tga", |image| {
-        let res = model.render_look_at(image, VIEWPORT, CAMERA, CENTER, UP, LIGHT_SOURCE);
+        let res = model.render_gouraud(image, &camera, &lights);
         zbuffer = res.1;
         res.0
     });
@@ -173,5 +235,219 @@ fn main() {
         image
     });
 
+    model.set_anti_aliasing(AntiAliasing::Supersampling(2));
+    wrap_render("Look at - Supersampling", "look_ssaa.tga", |image| {
+        model.render_gouraud(image, &camera, &lights).0
+    });
+
+    model.set_anti_aliasing(AntiAliasing::Multisampling(Samples::Four));
+    wrap_render("Look at - Multisampling", "look_msaa.tga", |image| {
+        model.render_gouraud(image, &camera, &lights).0
+    });
+    model.set_anti_aliasing(AntiAliasing::None);
+
+    // The texture is read from the level of the mipmap of the size of the pixels
+    model.set_sampler(Sampler {
+        filter: Filter::Trilinear,
+        wrap: Wrap::Repeat,
+    });
+    wrap_render("Look at - Trilinear", "look_trilinear.tga", |image| {
+        model.render_gouraud(image, &camera, &lights).0
+    });
+    model.set_sampler(Sampler::default());
+
+    let model = match normal_map {
+        Some(normal_map) => model.with_normal_map(normal_map),
+        None => model,
+    };
+
+    wrap_render("Normal map", "normal_map.tga", |image| {
+        let res = model.render_gouraud(image, &camera, &lights);
+        zbuffer = res.1;
+        res.0
+    });
+
+    wrap_render("Phong", "phong.tga", |image| {
+        let res = model.render_phong(image, &camera, &lights);
+        zbuffer = res.1;
+        res.0
+    });
+
+    // Thick dashed edges over the render
+    wrap_render("Phong - Wireframe", "phong_wireframe.tga", |image| {
+        let style = LineStyle::default()
+            .with_width(2.5)
+            .with_cap(LineCap::Round)
+            .with_dash(&[8.0, 6.0])
+            .with_anti_aliasing(true);
+        let (image, _) = model.render_phong(image, &camera, &lights);
+        model.render_wireframe(image, &camera, Color::hex(b"#ffd70080"), &style)
+    });
+
+    wrap_render_alpha("Phong - Cut-out", "phong_cutout.tga", true, |image| {
+        model.render_phong(image, &camera, &lights).0
+    });
+
+    println!("Shadow map\n");
+    // Orthographic camera in the light with the center in the middle of near and far
+    let light = Camera::orthographic(2.0, aspect, 0.5, 3.5).look_at(
+        CENTER + LIGHT_SOURCE * 2.0,
+        CENTER,
+        UP,
+    );
+    // Only the back faces seen from the light are in the shadow map
+    // so the lit faces don't shadow themselves
+    let occluder = {
+        let image_path = format!("obj/{0}/{0}.obj", MODEL);
+        Model::new(image_path.as_str(), None)
+            .unwrap_or_else(|error| panic!("Can't open model: {}", error))
+            .with_cull_mode(CullMode {
+                cull: Cull::Front,
+                ..CullMode::default()
+            })
+    };
+    let shadow_map = ShadowMap::new(&occluder, WIDTH, HEIGHT, &light);
+
+    wrap_render("Shadow", "shadow.tga", |image| {
+        let res = model.render_shadow(image, &camera, &lights, &shadow_map);
+        zbuffer = res.1;
+        res.0
+    });
+
+    wrap_render("Ambient occlusion", "ssao.tga", |image| {
+        let (image, zbuffer) = model.render_phong(image, &camera, &lights);
+        ssao::ambient_occlusion(image, &zbuffer, 16, 2.0)
+    });
+
+    println!("Scene\n");
+    // Three copies of the model side by side turned to the center
+    // and a small one in front of them
+    let scene_camera = Camera::perspective(FOV.to_radians(), aspect, 2.0, 12.0).look_at(
+        Vector3 {
+            x: 0.0,
+            y: 1.5,
+            z: 7.0,
+        },
+        CENTER,
+        UP,
+    );
+    let side = |x: f64, angle: f64| {
+        Matrix4::translation(Vector3 { x, y: 0.0, z: -1.0 })
+            * Matrix4::rotation(UP, angle.to_radians())
+    };
+    let small = Matrix4::translation(Vector3 {
+        x: 0.0,
+        y: -0.5,
+        z: 1.5,
+    }) * Matrix4::scale(Vector3 {
+        x: 0.5,
+        y: 0.5,
+        z: 0.5,
+    });
+    // A dim white light from the front, a red point light in the left
+    // and a blue spot light from the top in the small copy
+    let scene = Scene::new(scene_camera)
+        .with_light(Light::Directional {
+            direction: LIGHT_SOURCE,
+            color: Color::gray(255),
+            intensity: 0.6,
+        })
+        .with_light(Light::Point {
+            position: Vector3 {
+                x: -3.0,
+                y: 1.0,
+                z: 1.5,
+            },
+            color: Color::hex(b"#f42"),
+            intensity: 1.0,
+            attenuation: (1.0, 0.0, 0.1),
+        })
+        .with_light(Light::Spot {
+            position: Vector3 {
+                x: 0.0,
+                y: 3.0,
+                z: 1.5,
+            },
+            direction: Vector3 {
+                x: 0.0,
+                y: -1.0,
+                z: 0.0,
+            },
+            color: Color::hex(b"#48f"),
+            intensity: 1.5,
+            attenuation: (1.0, 0.0, 0.0),
+            angle: 20.0f64.to_radians(),
+            falloff: 5.0f64.to_radians(),
+        })
+        .with_instance(&model, Matrix4::identity())
+        .with_instance(&model, side(-2.0, 30.0))
+        .with_instance(&model, side(2.0, -30.0))
+        .with_instance(&model, small);
+
+    let light = Camera::orthographic(6.0, aspect, 1.0, 9.0).look_at(
+        CENTER + LIGHT_SOURCE * 5.0,
+        CENTER,
+        UP,
+    );
+    let shadow_map = ShadowMap::from_scene(&scene, WIDTH, HEIGHT, &light);
+
+    wrap_render("Scene", "scene.tga", |image| {
+        let res = scene.render_shadow(image, &shadow_map);
+        zbuffer = res.1;
+        res.0
+    });
+    wrap_render("Scene - Zbuffer", "scene_zbuffer.tga", |mut image| {
+        for (i, &z) in zbuffer.iter().enumerate() {
+            image.set(index(i), Color::gray(z as u8))
+        }
+        image
+    });
+
+    // The lights are summed without clipping and compressed by the tone mapping
+    wrap_render("Scene - HDR", "scene_hdr.tga", |image| {
+        let hdr = HdrImage::new(image.width, image.height);
+        let (hdr, _) = scene.render_shadow(hdr, &shadow_map);
+        hdr.tone_map(ToneMapping::Aces, 1.0, GAMMA)
+    });
+
+    println!("Transparency\n");
+    // A translucent copy of the model in front of the right one
+    let glass = {
+        let image_path = format!("obj/{0}/{0}.obj", MODEL);
+        let texture_path = format!("obj/{0}/{0}_diffuse.tga", MODEL);
+        Model::new(image_path.as_str(), Some(texture_path.as_str()))
+            .unwrap_or_else(|error| panic!("Can't open model: {}", error))
+            .with_opacity(0.5)
+            // The back of the glass is seen through the front
+            .with_cull_mode(CullMode {
+                cull: Cull::None,
+                ..CullMode::default()
+            })
+    };
+    let glass_transform = Matrix4::translation(Vector3 {
+        x: 1.2,
+        y: 0.2,
+        z: 1.5,
+    }) * Matrix4::scale(Vector3 {
+        x: 0.6,
+        y: 0.6,
+        z: 0.6,
+    });
+    let mut scene = scene.with_instance(&glass, glass_transform);
+
+    wrap_render("Transparency - Sorted", "transparency.tga", |image| {
+        scene.render_shadow(image, &shadow_map).0
+    });
+
+    scene.transparency = Transparency::WeightedBlended;
+    wrap_render("Transparency - OIT", "transparency_oit.tga", |image| {
+        scene.render_shadow(image, &shadow_map).0
+    });
+
+    scene.anti_aliasing = AntiAliasing::Multisampling(Samples::Four);
+    wrap_render("Transparency - MSAA", "transparency_msaa.tga", |image| {
+        scene.render_shadow(image, &shadow_map).0
+    });
+
     println!("Images created with success");
 }
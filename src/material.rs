@@ -0,0 +1,243 @@
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::path::Path;
+
+use crate::error::LoadError;
+use crate::image::Image;
+use crate::modules::{Color, Vector3};
+use crate::texture::{Sampler, Texture, TexturePoint};
+
+/// A normal map texture
+pub enum NormalMap {
+    /// The colors are the normals in the model coordinates
+    Object(Texture),
+    /// The colors are the normals in the frame (tangent, bitangent, normal)
+    Tangent(Texture),
+}
+
+impl NormalMap {
+    /// Load the normal map in the Truevision TGA file
+    ///
+    /// Like the tinyrenderer models, name_nm.tga has the normals
+    /// in the model coordinates and the others (like name_nm_tangent.tga)
+    /// in the tangent space
+    pub fn load_tga(path: &str) -> Result<Self, LoadError> {
+        let texture = Texture::new(Image::load_tga(path)?);
+        if path.ends_with("_nm.tga") {
+            Ok(Self::Object(texture))
+        } else {
+            Ok(Self::Tangent(texture))
+        }
+    }
+
+    /// Normal of the surface in the texture point t read with the sampler
+    /// using the interpolated normal and (tangent, bitangent) of the surface
+    ///
+    /// If it isn't possible to use the normal map return the normal
+    pub fn normal(
+        &self,
+        t: TexturePoint,
+        sampler: Sampler,
+        normal: Vector3,
+        tangent: Option<(Vector3, Vector3)>,
+    ) -> Vector3 {
+        // Convert a color with r, g, b ∈ [0, 255]
+        // to a vector with x, y, z ∈ [-1, 1]
+        let color_to_vector = |texture: &Texture| {
+            let Color { r, g, b, .. } = texture.sample(sampler, t);
+            Vector3 {
+                x: (r as f64) / 255.0 * 2.0 - 1.0,
+                y: (g as f64) / 255.0 * 2.0 - 1.0,
+                z: (b as f64) / 255.0 * 2.0 - 1.0,
+            }
+        };
+
+        match self {
+            Self::Object(texture) => color_to_vector(texture).normalize(),
+            Self::Tangent(texture) => {
+                let (tangent, bitangent) = match tangent {
+                    Some(tangent) => tangent,
+                    None => return normal,
+                };
+                let v = color_to_vector(texture);
+
+                // Interpolation breaks the orthogonality of the frame
+                // then it's rebuilt with Gram–Schmidt
+                let n = normal.normalize();
+                let t = (tangent - n * (n * tangent)).normalize();
+                let b = (bitangent - n * (n * bitangent) - t * (t * bitangent)).normalize();
+
+                (t * v.x + b * v.y + n * v.z).normalize()
+            }
+        }
+    }
+}
+
+/// A material loaded from a Wavefront mtl file
+pub struct Material {
+    /// (newmtl) Name used by usemtl
+    pub name: String,
+
+    /// (Ka) Color of the ambient light
+    pub ambient: Color,
+
+    /// (Kd) Color of the diffuse light,
+    /// it's multiplied by the diffuse texture
+    pub diffuse: Color,
+
+    /// (Ks) Color of the specular light
+    pub specular: Color,
+
+    /// (Ns) Exponent of the specular light used if there is no specular map
+    pub shininess: f64,
+
+    /// (d) Opacity of the material, 1 is opaque and 0 is transparent
+    pub opacity: f64,
+
+    /// (map_Kd) The diffuse texture image
+    pub diffuse_map: Option<Texture>,
+
+    /// (map_Bump) The normal map texture image
+    pub normal_map: Option<NormalMap>,
+
+    /// (map_Ks) The specular map texture image
+    ///
+    /// Like the tinyrenderer models,
+    /// the exponent of the specular light is 5 + the blue of the specular map
+    pub specular_map: Option<Texture>,
+
+    /// How the textures are read
+    pub sampler: Sampler,
+}
+
+impl Material {
+    /// Create a material with the default values
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            ambient: Color::gray(5),
+            diffuse: Color::gray(255),
+            specular: Color::gray(153),
+            shininess: 16.0,
+            opacity: 1.0,
+            diffuse_map: None,
+            normal_map: None,
+            specular_map: None,
+            sampler: Sampler::default(),
+        }
+    }
+
+    /// Color of the diffuse light in the texture point t,
+    /// the alpha is the opacity of the material
+    /// multiplied by the alpha of the texture
+    pub fn diffuse_color(&self, t: Option<TexturePoint>) -> Color {
+        let color = match (&self.diffuse_map, t) {
+            (Some(diffuse_map), Some(t)) => diffuse_map.sample(self.sampler, t) * self.diffuse,
+            _ => self.diffuse,
+        };
+        let alpha = (color.a as f64) * self.opacity.clamp(0.0, 1.0);
+        color.with_alpha(alpha.round() as u8)
+    }
+
+    /// Test if the faces with the material can be seen through,
+    /// because of the opacity or the alpha of the diffuse texture
+    pub fn is_transparent(&self) -> bool {
+        self.opacity < 1.0
+            || self.diffuse.a < 255
+            || self
+                .diffuse_map
+                .as_ref()
+                .is_some_and(|texture| texture.image().is_transparent())
+    }
+
+    /// Exponent of the specular light in the texture point t
+    pub fn shininess(&self, t: Option<TexturePoint>) -> f64 {
+        match (&self.specular_map, t) {
+            // The base exponent keeps a black texel from lighting the whole face
+            (Some(specular_map), Some(t)) => 5.0 + specular_map.sample(self.sampler, t).b as f64,
+            _ => self.shininess,
+        }
+    }
+
+    /// Load the materials of a Wavefront mtl file
+    ///
+    /// The path of the textures are relative to the mtl file
+    pub fn load_mtl(path: &str) -> Result<Vec<Self>, LoadError> {
+        let file = BufReader::new(File::open(path).map_err(|error| LoadError::io(path, error))?);
+        let directory = Path::new(path).parent().unwrap_or_else(|| Path::new(""));
+
+        /// Function to reduce repeated code
+        fn f_parse(data: Option<&str>) -> Result<f64, String> {
+            let data = data.ok_or("Invalid Wavefront Mtl: missing a number")?;
+            data.trim()
+                .parse::<f64>()
+                .map_err(|_| format!("Invalid Wavefront Mtl: value {:?} isn't a float", data))
+        }
+
+        /// Convert a color with r, g, b ∈ [0, 1] to a Color
+        fn color_parse<'a>(mut data: impl Iterator<Item = &'a str>) -> Result<Color, String> {
+            let mut channel = || -> Result<u8, String> {
+                Ok((f_parse(data.next())?.clamp(0.0, 1.0) * 255.0).round() as u8)
+            };
+            Ok(Color::rgba(channel()?, channel()?, channel()?, 255))
+        }
+
+        /// Path of a texture, the options before the file name are ignored
+        fn map_parse<'a>(
+            directory: &Path,
+            data: impl Iterator<Item = &'a str>,
+        ) -> Result<String, String> {
+            let name = data
+                .last()
+                .ok_or("Invalid Wavefront Mtl: missing texture file name")?;
+            Ok(directory.join(name).to_string_lossy().into_owned())
+        }
+
+        let mut materials: Vec<Self> = Vec::new();
+        for (number, line) in file.lines().enumerate() {
+            let line = line.map_err(|error| LoadError::io(path, error))?;
+            let invalid = |reason: String| LoadError::invalid(path, Some(number + 1), reason);
+            let mut data = line.split_whitespace();
+
+            let keyword = match data.next() {
+                Some(keyword) => keyword,
+                None => continue,
+            };
+
+            if keyword == "newmtl" {
+                let name = data.next().unwrap_or("");
+                materials.push(Self::new(name));
+                continue;
+            }
+
+            let material = match materials.last_mut() {
+                Some(material) => material,
+                None => continue,
+            };
+
+            match keyword {
+                "Ka" => material.ambient = color_parse(data).map_err(invalid)?,
+                "Kd" => material.diffuse = color_parse(data).map_err(invalid)?,
+                "Ks" => material.specular = color_parse(data).map_err(invalid)?,
+                "Ns" => material.shininess = f_parse(data.next()).map_err(invalid)?,
+                "d" => material.opacity = f_parse(data.next()).map_err(invalid)?,
+                "Tr" => material.opacity = 1.0 - f_parse(data.next()).map_err(invalid)?,
+                "map_Kd" => {
+                    let texture_path = map_parse(directory, data).map_err(invalid)?;
+                    material.diffuse_map = Some(Texture::new(Image::load_tga(&texture_path)?));
+                }
+                "map_Bump" | "map_bump" | "bump" => {
+                    let texture_path = map_parse(directory, data).map_err(invalid)?;
+                    material.normal_map = Some(NormalMap::load_tga(&texture_path)?);
+                }
+                "map_Ks" => {
+                    let texture_path = map_parse(directory, data).map_err(invalid)?;
+                    material.specular_map = Some(Texture::new(Image::load_tga(&texture_path)?));
+                }
+                _ => continue,
+            }
+        }
+
+        Ok(materials)
+    }
+}
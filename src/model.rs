@@ -1,10 +1,62 @@
+use std::collections::{HashMap, HashSet};
 use std::fs::File;
 use std::io::{BufRead, BufReader};
+use std::path::Path;
+use std::str::FromStr;
+
+use crate::camera::Camera;
+use crate::error::LoadError;
+use crate::image::{self, AntiAliasing, Blend, CullMode, Image, LineStyle};
+use crate::light::{self, Light};
+use crate::material::{Material, NormalMap};
+use crate::modules::{Color, Matrix4, Point, Vector2, Vector3};
+use crate::shader::{
+    FlatShader, GouraudShader, GouraudTextureShader, PhongShader, ShadedFace, Shader, TextureShader,
+};
+use crate::shadow::ShadowMap;
+use crate::texture::{Sampler, Texture};
+use crate::transparency::{self, Transparency};
+
+type Element = (usize, Option<usize>, Option<usize>);
+
+/// A triangle face of the model
+///
+/// The polygons in the obj file are triangulated when they are loaded
+struct Face {
+    /// A list of 0-based indexes
+    /// (vertex, Option<texture_vertex>, Option<normal>)
+    elements: Vec<Element>,
+
+    /// The edge from the element i to the next is an edge of the polygon,
+    /// the other edges were added by the triangulation
+    outline: [bool; 3],
+
+    /// (usemtl) Index of the material of the face
+    material: usize,
 
-use crate::image::Image;
-use crate::modules::{mat, Color, Matrix, Vector2, Vector3};
+    /// (o) Index of the object of the face
+    object: usize,
+
+    /// (g) Index of the groups of the face
+    group: usize,
+
+    /// (s) Smoothing group of the face, 0 means smoothing off
+    smoothing: u32,
+}
+
+/// A vertex of a face
+#[derive(Copy, Clone, Debug)]
+pub struct Vertex {
+    pub position: Vector3,
+    pub texture: Option<Vector2>,
+    pub normal: Vector3,
+    /// (tangent, bitangent) of the vertex,
+    /// only exists if the model have texture vertices
+    pub tangent: Option<(Vector3, Vector3)>,
+    /// Index of the material of the face
+    pub material: usize,
+}
 
-type Element = (isize, Option<isize>, Option<isize>);
 /// Representation of a 3D model loaded from a Wavefront obj
 pub struct Model {
     /// (v) vertices of the model
@@ -16,371 +68,448 @@ pub struct Model {
     /// (vn) Normals of the vertices
     normals: Vec<Vector3>,
 
-    /// (f) A list of faces that is a list of indexes
-    /// (vertex, Option<texture_vertex>, Option<normal>)
-    faces: Vec<Vec<Element>>,
+    /// (f) A list of faces
+    faces: Vec<Face>,
+
+    /// (tangent, bitangent) of the vertices
+    /// computed using the texture vertices
+    tangents: Vec<(Vector3, Vector3)>,
+
+    /// (mtllib) Materials of the model
+    ///
+    /// The first is the default material
+    /// used by the faces without usemtl
+    materials: Vec<Material>,
+
+    /// (o) Names of the objects
+    ///
+    /// The first is the unnamed object of the faces before any o
+    objects: Vec<String>,
 
-    /// The diffuse texture image
-    diffuse: Option<Image>,
+    /// (g) Names of the groups,
+    /// each g statement can put the faces in more than one group
+    ///
+    /// The first is the empty list of the faces before any g
+    groups: Vec<Vec<String>>,
+
+    /// Names of the objects and groups that aren't rendered
+    hidden: HashSet<String>,
+
+    /// Faces discarded by the rasterizer
+    cull_mode: CullMode,
+
+    /// How the faces with transparent materials are drawn
+    transparency: Transparency,
+
+    /// How the edges of the faces are smoothed by the renders with shaders
+    anti_aliasing: AntiAliasing,
+
+    /// Threads that draw the opaque faces in the renders with shaders
+    threads: usize,
 }
 
 impl Model {
-    /// Wireframe Render in orthographic projection
-    pub fn render_wireframe(&self, mut image: Image, color: Color) -> Image {
-        for face in self.faces() {
-            let mut v = match face.last() {
-                Some(v) => v.0,
+    /// Wireframe Render
+    ///
+    /// The edges are drawn with the line style
+    pub fn render_wireframe(
+        &self,
+        mut image: Image,
+        camera: &Camera,
+        color: Color,
+        style: &LineStyle,
+    ) -> Image {
+        let transform = image.viewport() * camera.transform();
+        for face in self.faces.iter().filter(|face| self.is_visible(face)) {
+            let points: Option<Vec<Vector2>> = face
+                .elements
+                .iter()
+                .map(|&(vi, _, _)| screen_position(&transform, self.vertices[vi]))
+                .collect();
+            // A vertex is behind the eye
+            let points = match points {
+                Some(points) => points,
                 None => continue,
             };
-            for (u, _, _) in face {
-                image.line(
-                    u.to_image_point(image.width, image.height),
-                    v.to_image_point(image.width, image.height),
-                    color,
-                );
-                v = u;
+
+            // Only the edges of the polygons in the obj file,
+            // not the ones added by the triangulation
+            let mut v = (2, points[2]);
+            for (i, &u) in points.iter().enumerate() {
+                if face.outline[v.0] {
+                    image.line_styled(u, v.1, color, style);
+                }
+                v = (i, u);
             }
         }
         image
     }
 
-    /// Draw triangles in orthographic projection
+    /// Draw triangles
     /// (Triangles can overlap others)
-    pub fn render_triangles(&self, mut image: Image, color: Color, light_source: Vector3) -> Image {
+    pub fn render_triangles(
+        &self,
+        mut image: Image,
+        camera: &Camera,
+        color: Color,
+        lights: &[Light],
+    ) -> Image {
+        let transform = image.viewport() * camera.transform();
         for face in self.faces() {
-            let (u, _, _) = face[0];
-            let (v, _, _) = face[1];
-            let (w, _, _) = face[2];
+            let u = face[0].position;
+            let v = face[1].position;
+            let w = face[2].position;
 
             let normal = Vector3::normal(u, v, w);
-            let intensity = normal * light_source;
-
-            if intensity > 0.0 {
-                let u = u.to_image_point(image.width, image.height);
-                let v = v.to_image_point(image.width, image.height);
-                let w = w.to_image_point(image.width, image.height);
+            let intensities = light::intensities(lights, (u + v + w) / 3.0, normal);
+
+            let (u, v, w) = match (
+                screen_point(&transform, u),
+                screen_point(&transform, v),
+                screen_point(&transform, w),
+            ) {
+                (Some(u), Some(v), Some(w)) => (u, v, w),
+                _ => continue,
+            };
 
-                image.triangle((u, v, w), color.light(intensity));
+            if !self.cull_mode.is_culled((v - u).cross(w - u)) {
+                image.triangle(
+                    (u, v, w),
+                    light::shade(color, lights, intensities.into_iter()),
+                );
             }
         }
         image
     }
 
-    /// Render a image in orthographic projection
-    /// using a color
+    /// Render the model using a shader
+    /// in an image with the type of pixels of the shader
+    ///
+    /// The faces with transparent materials are drawn after the opaque faces
+    /// and the image is smoothed by the anti-aliasing of the model
+    ///
+    /// The opaque faces are drawn by the threads of the model
+    ///
+    /// Return Zbuffer for debug purpose
+    pub fn render<P, S>(&self, image: Image<P>, shader: &mut S) -> (Image<P>, Vec<f64>)
+    where
+        P: Blend + Send,
+        S: Shader<P> + Clone + Send,
+    {
+        image.render_anti_aliased(self.anti_aliasing, |mut image| {
+            let mut zbuffer = image.zbuffer();
+            let transparent_materials = self.transparent_materials();
+
+            let (transparent, opaque): (Vec<ShadedFace>, Vec<ShadedFace>) = self
+                .faces()
+                .map(|face| ShadedFace {
+                    shader: 0,
+                    vertices: face,
+                    cull_mode: self.cull_mode,
+                })
+                .partition(|face| transparent_materials[face.vertices[0].material]);
+
+            let shaders = std::slice::from_ref(&*shader);
+            image.faces_tiled(&mut zbuffer, shaders, &opaque, self.threads);
+
+            transparency::draw_transparent(
+                &mut image,
+                &mut zbuffer,
+                std::slice::from_mut(shader),
+                transparent,
+                self.transparency,
+            );
+
+            (image, zbuffer)
+        })
+    }
+
+    /// Render a image using a color
     ///
     /// Return Zbuffer for debug purpose
     pub fn render_color(
         &self,
-        mut image: Image,
-        viewport: (Vector3, Vector3),
+        image: Image,
+        camera: &Camera,
         color: Color,
-        light_source: Vector3,
+        lights: &[Light],
     ) -> (Image, Vec<f64>) {
-        let mut zbuffer: Vec<f64> = vec![f64::NEG_INFINITY; (image.width * image.height) as usize];
-        let transform = matrix_viewport(viewport.0, viewport.1);
-
-        for face in self.faces() {
-            let (u, _, _) = face[0];
-            let (v, _, _) = face[1];
-            let (w, _, _) = face[2];
-
-            let u = (&transform * u.to_matrix(true)).to_vector3();
-            let v = (&transform * v.to_matrix(true)).to_vector3();
-            let w = (&transform * w.to_matrix(true)).to_vector3();
-
-            let normal = Vector3::normal(u, v, w);
-            let intensity = normal * light_source;
-            let draw_color = color.light(intensity);
-
-            image.triangle_zbuffer(&mut zbuffer, (u, v, w), draw_color);
-        }
-
-        (image, zbuffer)
+        let transform = camera.transform();
+        self.render(image, &mut FlatShader::new(transform, color, lights))
     }
 
-    /// Render a image in orthographic projection
-    /// using a diffuse texture image
+    /// Render a image using a diffuse texture image
     ///
     /// Return Zbuffer for debug pruporse
     pub fn render_texture(
         &self,
-        mut image: Image,
-        viewport: (Vector3, Vector3),
-        light_source: Vector3,
+        image: Image,
+        camera: &Camera,
+        lights: &[Light],
     ) -> (Image, Vec<f64>) {
-        let mut zbuffer: Vec<f64> = vec![f64::NEG_INFINITY; (image.width * image.height) as usize];
-        let transform = matrix_viewport(viewport.0, viewport.1);
-
-        for face in self.faces() {
-            let diffuse = match &self.diffuse {
-                Some(image) => image,
-                None => panic!("Model have no diffuse texture image"),
-            };
-
-            let (u, ut, _) = face[0];
-            let (v, vt, _) = face[1];
-            let (w, wt, _) = face[2];
-
-            let u = (&transform * u.to_matrix(true)).to_vector3();
-            let v = (&transform * v.to_matrix(true)).to_vector3();
-            let w = (&transform * w.to_matrix(true)).to_vector3();
-
-            let ut = ut.expect("Model have no texture vertex");
-            let vt = vt.expect("Model have no texture vertex");
-            let wt = wt.expect("Model have no texture vertex");
-
-            let normal = Vector3::normal(u, v, w);
-            let intensity = normal * light_source;
-
-            image.triangle_zbuffer_texture(
-                &mut zbuffer,
-                &diffuse,
-                (u, v, w),
-                (ut, vt, wt),
-                intensity,
-            );
-        }
-
-        (image, zbuffer)
+        let transform = camera.transform();
+        let mut shader = TextureShader::new(transform, &self.materials, lights);
+        self.render(image, &mut shader)
     }
 
-    /// Render a image in pespective projection
-    /// using a diffuse texture image
+    /// Render a image using Gouraud shading
     ///
     /// Return Zbuffer for debug purpose
-    pub fn render_perspective(
+    pub fn render_gouraud_color(
         &self,
-        mut image: Image,
-        viewport: (Vector3, Vector3),
-        camera_z: f64,
-        light_source: Vector3,
+        image: Image,
+        camera: &Camera,
+        color: Color,
+        lights: &[Light],
     ) -> (Image, Vec<f64>) {
-        let mut zbuffer: Vec<f64> = vec![f64::NEG_INFINITY; (image.width * image.height) as usize];
-        let transform = matrix_viewport(viewport.0, viewport.1) * matrix_perspective(camera_z);
-
-        for face in self.faces() {
-            let diffuse = match &self.diffuse {
-                Some(image) => image,
-                None => panic!("Model have no diffuse texture image"),
-            };
-
-            let (u, ut, _) = face[0];
-            let (v, vt, _) = face[1];
-            let (w, wt, _) = face[2];
-
-            let normal = Vector3::normal(u, v, w);
-            let intensity = normal * light_source;
-
-            let u = (&transform * u.to_matrix(true)).to_vector3();
-            let v = (&transform * v.to_matrix(true)).to_vector3();
-            let w = (&transform * w.to_matrix(true)).to_vector3();
-
-            let ut = ut.expect("Model have no texture vertex");
-            let vt = vt.expect("Model have no texture vertex");
-            let wt = wt.expect("Model have no texture vertex");
-
-            image.triangle_zbuffer_texture(
-                &mut zbuffer,
-                &diffuse,
-                (u, v, w),
-                (ut, vt, wt),
-                intensity,
-            );
-        }
-
-        (image, zbuffer)
+        let transform = camera.transform();
+        self.render(image, &mut GouraudShader::new(transform, color, lights))
     }
 
-    /// Render a image in orthographic projection
-    /// using Gouraud shading
+    /// Render a image using a diffuse texture
+    /// and Gouraud shading
     ///
     /// Return Zbuffer for debug purpose
-    pub fn render_gouraud_color(
+    pub fn render_gouraud(
         &self,
-        mut image: Image,
-        viewport: (Vector3, Vector3),
-        color: Color,
-        light_source: Vector3,
+        image: Image,
+        camera: &Camera,
+        lights: &[Light],
     ) -> (Image, Vec<f64>) {
-        let mut zbuffer: Vec<f64> = vec![f64::NEG_INFINITY; (image.width * image.height) as usize];
-        let transform = matrix_viewport(viewport.0, viewport.1);
+        // Transformation chain: Projection * View * Model * v
+        let transform = camera.transform();
+        let mut shader = GouraudTextureShader::new(transform, &self.materials, lights);
+        self.render(image, &mut shader)
+    }
 
-        for face in self.faces() {
-            let (u, _, un) = face[0];
-            let (v, _, vn) = face[1];
-            let (w, _, wn) = face[2];
+    /// Render a image using a diffuse texture
+    /// and Phong shading with specular highlights
+    ///
+    /// Return Zbuffer for debug purpose
+    pub fn render_phong<P>(
+        &self,
+        image: Image<P>,
+        camera: &Camera,
+        lights: &[Light],
+    ) -> (Image<P>, Vec<f64>)
+    where
+        P: Blend + Send,
+        for<'m> PhongShader<'m>: Shader<P>,
+    {
+        let mut shader = self.phong_shader(camera, lights);
+        self.render(image, &mut shader)
+    }
 
-            let u = (&transform * u.to_matrix(true)).to_vector3();
-            let v = (&transform * v.to_matrix(true)).to_vector3();
-            let w = (&transform * w.to_matrix(true)).to_vector3();
+    /// Render a image using a diffuse texture,
+    /// Phong shading with specular highlights
+    /// and the shadows of the first light in the shadow map
+    ///
+    /// Return Zbuffer for debug purpose
+    pub fn render_shadow<P>(
+        &self,
+        image: Image<P>,
+        camera: &Camera,
+        lights: &[Light],
+        shadow_map: &ShadowMap,
+    ) -> (Image<P>, Vec<f64>)
+    where
+        P: Blend + Send,
+        for<'m> PhongShader<'m>: Shader<P>,
+    {
+        let mut shader = self.phong_shader(camera, lights);
+        shader.shadow_map = Some(shadow_map);
+        self.render(image, &mut shader)
+    }
 
-            image.triangle_zbuffer_gourad_color(
-                &mut zbuffer,
-                (u, v, w),
-                (un, vn, wn),
-                color,
-                light_source,
-            );
-        }
+    /// Phong shader of the model seen from the camera
+    fn phong_shader(&self, camera: &Camera, lights: &[Light]) -> PhongShader<'_> {
+        PhongShader::new(camera.transform(), &self.materials, lights, camera.eye)
+    }
 
-        (image, zbuffer)
+    /// Use a normal map in the default material
+    pub fn with_normal_map(mut self, normal_map: NormalMap) -> Self {
+        self.materials[0].normal_map = Some(normal_map);
+        self
     }
 
-    /// Render a image in pespective projection
-    /// using a diffuse texture
-    /// and Gouraud shading
+    /// Remove the normal map of the default material and return it
+    pub fn take_normal_map(&mut self) -> Option<NormalMap> {
+        self.materials[0].normal_map.take()
+    }
+
+    /// Set the faces discarded by the rasterizer
     ///
-    /// Return Zbuffer for debug purpose
-    pub fn render_gouraud(
-        &self,
-        mut image: Image,
-        viewport: (Vector3, Vector3),
-        camera_z: f64,
-        light_source: Vector3,
-    ) -> (Image, Vec<f64>) {
-        let mut zbuffer: Vec<f64> = vec![f64::NEG_INFINITY; (image.width * image.height) as usize];
-        let transform = matrix_viewport(viewport.0, viewport.1) * matrix_perspective(camera_z);
+    /// By default the back faces of counterclockwise triangles are discarded
+    pub fn with_cull_mode(mut self, cull_mode: CullMode) -> Self {
+        self.cull_mode = cull_mode;
+        self
+    }
 
-        for face in self.faces() {
-            let diffuse = match &self.diffuse {
-                Some(image) => image,
-                None => panic!("Model have no diffuse texture image"),
-            };
+    /// Materials of the model, the first is the default material
+    pub fn materials(&self) -> &[Material] {
+        &self.materials
+    }
+
+    /// Faces discarded by the rasterizer
+    pub fn cull_mode(&self) -> CullMode {
+        self.cull_mode
+    }
 
-            let (u, ut, un) = face[0];
-            let (v, vt, vn) = face[1];
-            let (w, wt, wn) = face[2];
+    /// Set how the faces with transparent materials are drawn
+    #[allow(dead_code)]
+    pub fn with_transparency(mut self, transparency: Transparency) -> Self {
+        self.transparency = transparency;
+        self
+    }
 
-            let u = (&transform * u.to_matrix(true)).to_vector3();
-            let v = (&transform * v.to_matrix(true)).to_vector3();
-            let w = (&transform * w.to_matrix(true)).to_vector3();
+    /// Set how the edges of the faces are smoothed
+    /// by the renders with shaders
+    pub fn set_anti_aliasing(&mut self, anti_aliasing: AntiAliasing) {
+        self.anti_aliasing = anti_aliasing;
+    }
 
-            let ut = ut.expect("Model have no texture vertex");
-            let vt = vt.expect("Model have no texture vertex");
-            let wt = wt.expect("Model have no texture vertex");
+    /// Set the number of threads that draw the opaque faces,
+    /// 1 draws them one by one in this thread
+    #[allow(dead_code)]
+    pub fn set_threads(&mut self, threads: usize) {
+        self.threads = threads;
+    }
 
-            image.triangle_zbuffer_gourad_texture(
-                &mut zbuffer,
-                &diffuse,
-                (u, v, w),
-                (ut, vt, wt),
-                (un, vn, wn),
-                light_source,
-            );
+    /// Set how the textures of all materials are read
+    pub fn set_sampler(&mut self, sampler: Sampler) {
+        for material in &mut self.materials {
+            material.sampler = sampler;
         }
+    }
 
-        (image, zbuffer)
+    /// Multiply the opacity of all materials,
+    /// 1 keeps the model as it's and 0 makes it invisible
+    pub fn with_opacity(mut self, opacity: f64) -> Self {
+        for material in &mut self.materials {
+            material.opacity *= opacity;
+        }
+        self
     }
-    /// Render a image in pespective projection
-    /// using a diffuse texture
-    /// and Gouraud shading
-    ///
-    /// Return Zbuffer for debug purpose
-    pub fn render_look_at(
-        &self,
-        mut image: Image,
-        viewport: (Vector3, Vector3),
-        eye: Vector3,
-        center: Vector3,
-        up: Vector3,
-        light_source: Vector3,
-    ) -> (Image, Vec<f64>) {
-        let mut zbuffer: Vec<f64> = vec![f64::NEG_INFINITY; (image.width * image.height) as usize];
-        // Transformation chain: Viewport * Projection * View * Model * v
-        let model_view = matrix_model_view(eye, center, up);
-        let transform =
-            matrix_viewport(viewport.0, viewport.1) * matrix_perspective(eye.z) * &model_view;
 
-        let model_view = model_view.transpose(); // Now it'll be used to convert normals
-        for face in self.faces() {
-            let diffuse = match &self.diffuse {
-                Some(image) => image,
-                None => panic!("Model have no diffuse texture image"),
-            };
+    /// For each material, test if it's transparent
+    pub fn transparent_materials(&self) -> Vec<bool> {
+        self.materials
+            .iter()
+            .map(|material| material.is_transparent())
+            .collect()
+    }
 
-            let (u, ut, un) = face[0];
-            let (v, vt, vn) = face[1];
-            let (w, wt, wn) = face[2];
+    /// Calculate the tangent and the bitangent of all vertices
+    /// using the texture vertices
+    ///
+    /// The tangent and the bitangent are the directions in the model
+    /// where the texture vertex u and v grow
+    fn compute_tangents(&mut self) {
+        let zero = Vector3 {
+            x: 0.0,
+            y: 0.0,
+            z: 0.0,
+        };
+        let mut tangents: Vec<(Vector3, Vector3)> = vec![(zero, zero); self.vertices.len()];
 
-            let u = (&transform * u.to_matrix(true)).to_vector3();
-            let v = (&transform * v.to_matrix(true)).to_vector3();
-            let w = (&transform * w.to_matrix(true)).to_vector3();
+        for Face { elements: vec, .. } in &self.faces {
+            let (u_index, ut_index, _) = vec[0];
+            let (v_index, vt_index, _) = vec[1];
+            let (w_index, wt_index, _) = vec[2];
 
-            let ut = ut.expect("Model have no texture vertex");
-            let vt = vt.expect("Model have no texture vertex");
-            let wt = wt.expect("Model have no texture vertex");
+            let (ut_index, vt_index, wt_index) = match (ut_index, vt_index, wt_index) {
+                (Some(ut), Some(vt), Some(wt)) => (ut, vt, wt),
+                _ => continue,
+            };
 
-            let un = (&model_view * un.to_matrix(false)).to_vector3();
-            let vn = (&model_view * vn.to_matrix(false)).to_vector3();
-            let wn = (&model_view * wn.to_matrix(false)).to_vector3();
-            // dbg!(un, vn, wn);
+            let u = self.vertices[u_index];
+            let v = self.vertices[v_index];
+            let w = self.vertices[w_index];
+
+            let ut = self.textures[ut_index];
+            let vt = self.textures[vt_index];
+            let wt = self.textures[wt_index];
+
+            // Solve the system
+            // uv = tangent * (vt - ut).x + bitangent * (vt - ut).y
+            // uw = tangent * (wt - ut).x + bitangent * (wt - ut).y
+            let uv = v - u;
+            let uw = w - u;
+            let duv = vt - ut;
+            let duw = wt - ut;
+
+            let det = duv.cross(duw);
+            if det.abs() < f64::EPSILON {
+                continue;
+            }
+            let tangent = (uv * duw.y - uw * duv.y) / det;
+            let bitangent = (uw * duv.x - uv * duw.x) / det;
 
-            image.triangle_zbuffer_gourad_texture(
-                &mut zbuffer,
-                &diffuse,
-                (u, v, w),
-                (ut, vt, wt),
-                (un, vn, wn),
-                light_source,
-            );
+            for &(vi, _, _) in vec {
+                let (sum_tangent, sum_bitangent) = tangents[vi];
+                tangents[vi] = (sum_tangent + tangent, sum_bitangent + bitangent);
+            }
         }
 
-        (image, zbuffer)
+        self.tangents = tangents
+            .into_iter()
+            .map(|(tangent, bitangent)| (tangent.normalize(), bitangent.normalize()))
+            .collect();
     }
 
     /// Calculate the normals of all vertices that isn't calculated yet
     ///
     /// Actually this method calculate the normals of all vertices
     /// and update only the normals that is None
+    ///
+    /// The normal of a vertex is the average of the normals of the faces
+    /// in the same smoothing group that share the vertex,
+    /// then there are hard edges between smoothing groups.
+    /// Faces with the smoothing off use the normal of the face
     fn compute_normals(&mut self) {
-        // Average[i] is the sum of the normals and count of normals of vertices[i]
-        let mut average: Vec<(Vector3, usize)> = vec![
-            (
-                Vector3 {
-                    x: 0.0,
-                    y: 0.0,
-                    z: 0.0
-                },
-                0
-            );
-            self.vertices.len()
-        ];
+        // Average[(i, s)] is the sum of the normals and count of normals of vertices[i]
+        // in the faces of the smoothing group s
+        let mut average: HashMap<(usize, u32), (Vector3, usize)> = HashMap::new();
+        let mut face_normals: Vec<Vector3> = Vec::new();
 
-        for vec in &mut self.faces {
+        for face in &self.faces {
+            let vec = &face.elements;
             let (u_index, _, _) = vec[0];
             let (v_index, _, _) = vec[1];
             let (w_index, _, _) = vec[2];
 
-            let u = self.vertices[convert_index(u_index, self.vertices.len())];
-            let v = self.vertices[convert_index(v_index, self.vertices.len())];
-            let w = self.vertices[convert_index(w_index, self.vertices.len())];
+            let u = self.vertices[u_index];
+            let v = self.vertices[v_index];
+            let w = self.vertices[w_index];
 
             let normal = Vector3::normal(u, v, w);
+            face_normals.push(normal);
 
-            for i in 0..vec.len() {
-                let (vi, vti, vni) = vec[i];
-                let index = convert_index(vi, self.vertices.len());
-
-                let (sum, count) = average[index];
-                average[index] = ((sum + normal), count + 1);
-                if let Some(vni) = vni {
-                    let vni = (convert_index(vni, self.normals.len()) + 1) as isize;
-                    vec[i] = (vi, vti, Some(vni));
-                }
+            for &(vi, _, _) in vec {
+                let zero = Vector3 {
+                    x: 0.0,
+                    y: 0.0,
+                    z: 0.0,
+                };
+                let (sum, count) = average.entry((vi, face.smoothing)).or_insert((zero, 0));
+                *sum = *sum + normal;
+                *count += 1;
             }
         }
 
-        for vec in &mut self.faces {
-            for i in 0..vec.len() {
-                let (vi, vti, vni) = vec[i];
+        for (face, &normal) in self.faces.iter_mut().zip(&face_normals) {
+            for element in face.elements.iter_mut() {
+                let (vi, vti, vni) = *element;
                 if vni.is_none() {
-                    let v_index = convert_index(vi, self.vertices.len());
-                    let (sum, count) = average[v_index];
-                    self.normals.push(sum / (count as f64));
+                    if face.smoothing == 0 {
+                        self.normals.push(normal);
+                    } else {
+                        let (sum, count) = average[&(vi, face.smoothing)];
+                        self.normals.push(sum / (count as f64));
+                    }
 
-                    let vni = Some(self.normals.len() as isize);
-                    vec[i] = (vi, vti, vni);
+                    let vni = Some(self.normals.len() - 1);
+                    *element = (vi, vti, vni);
                 }
             }
         }
@@ -388,124 +517,231 @@ impl Model {
 
     /// Create a model from a Wavefront obj file
     /// and use the Truevision TGA file in texture_path if it isn't None
-    pub fn new(model_path: &str, texture_path: Option<&str>) -> std::io::Result<Self> {
-        let diffuse = match texture_path {
-            Some(path) => Some(Image::load_tga(path)?),
-            None => None,
-        };
+    /// as the diffuse texture of the default material
+    ///
+    /// Like the tinyrenderer models, if the texture is name_diffuse.tga
+    /// and there is a name_spec.tga next to it, it's the specular map,
+    /// and name_nm_tangent.tga or else name_nm.tga is the normal map
+    ///
+    /// The materials in the mtl files referenced by mtllib are loaded too
+    pub fn new(model_path: &str, texture_path: Option<&str>) -> Result<Self, LoadError> {
+        let mut default = Material::new("");
+        if let Some(path) = texture_path {
+            default.diffuse_map = Some(Texture::new(Image::load_tga(path)?));
+            if let Some(name) = path.strip_suffix("_diffuse.tga") {
+                let specular_path = format!("{}_spec.tga", name);
+                if Path::new(&specular_path).exists() {
+                    let specular = Image::load_tga(&specular_path)?;
+                    default.specular_map = Some(Texture::new(specular));
+                }
+
+                let normal_paths = [
+                    format!("{}_nm_tangent.tga", name),
+                    format!("{}_nm.tga", name),
+                ];
+                if let Some(path) = normal_paths.iter().find(|path| Path::new(path).exists()) {
+                    default.normal_map = Some(NormalMap::load_tga(path)?);
+                }
+            }
+        }
 
-        let file = BufReader::new(File::open(model_path)?);
+        let file = BufReader::new(
+            File::open(model_path).map_err(|error| LoadError::io(model_path, error))?,
+        );
+        let directory = Path::new(model_path)
+            .parent()
+            .unwrap_or_else(|| Path::new(""));
 
         let mut model = Self {
             vertices: Vec::new(),
             faces: Vec::new(),
             textures: Vec::new(),
             normals: Vec::new(),
-            diffuse,
+            tangents: Vec::new(),
+            materials: vec![default],
+            objects: vec![String::new()],
+            groups: vec![Vec::new()],
+            hidden: HashSet::new(),
+            cull_mode: CullMode::default(),
+            transparency: Transparency::default(),
+            anti_aliasing: AntiAliasing::default(),
+            threads: image::available_threads(),
         };
 
+        // Index of the material, object and group used by the next faces
+        let mut material = 0;
+        let mut object = 0;
+        let mut group = 0;
+        // Without s statements all faces are smoothed together
+        let mut smoothing = 1;
+
+        /// Function to reduce repeated code
+        fn parse<T: FromStr>(
+            data: Option<&str>,
+            missing: &str,
+            invalid: &str,
+        ) -> Result<T, String> {
+            let data = data.ok_or(format!("Invalid Wavefront Obj: {}", missing))?;
+            data.trim()
+                .parse::<T>()
+                .map_err(|_| format!("Invalid Wavefront Obj: {} {:?}", invalid, data))
+        }
+
+        /// Parse a face index and convert it to a 0-based index
+        fn index_parse(data: &str, max: usize, name: &str) -> Result<usize, String> {
+            let index = parse::<isize>(
+                Some(data),
+                "",
+                &format!("The face {} index isn't an integer", name),
+            )?;
+            convert_index(index, max).ok_or(format!(
+                "Invalid Wavefront Obj: The face {} index {} is out of range (there are {})",
+                name, index, max
+            ))
+        }
+
         let mut no_computed_normals = false;
-        for line in file.lines() {
-            let line = line?;
+        for (number, line) in file.lines().enumerate() {
+            let line = line.map_err(|error| LoadError::io(model_path, error))?;
+            let invalid = |reason: String| LoadError::invalid(model_path, Some(number + 1), reason);
             let mut data = line.split(" ").filter(|string| !string.is_empty());
 
             match data.next() {
                 Some("v") => model.vertices.push({
-                    /// Function to reduce repeated code
-                    fn v_parse(data: Option<&str>) -> f64 {
-                        data.expect(
-                            "Invalid Wavefront Obj: Vertex have less than three coordinates",
+                    let mut v_parse = || {
+                        parse::<f64>(
+                            data.next(),
+                            "Vertex have less than three coordinates",
+                            "Vertex coordinate isn't a float",
                         )
-                        .trim()
-                        .parse::<f64>()
-                        .expect("Invalid Wavefront Obj: Vertex coordinate isn't a float")
-                    }
+                        .map_err(invalid)
+                    };
 
                     Vector3 {
-                        x: v_parse(data.next()),
-                        y: v_parse(data.next()),
-                        z: v_parse(data.next()),
+                        x: v_parse()?,
+                        y: v_parse()?,
+                        z: v_parse()?,
                     }
                 }),
                 Some("vt") => model.textures.push({
-                    /// Function to reduce repeated code
-                    fn vt_parse(data: Option<&str>) -> f64 {
-                        data.expect(
-                            "Invalid Wavefront Obj: Texture Vertex have less than two coordinates",
+                    let mut vt_parse = || {
+                        parse::<f64>(
+                            data.next(),
+                            "Texture Vertex have less than two coordinates",
+                            "Texture Vertex coordinate isn't a float",
                         )
-                        .trim()
-                        .parse::<f64>()
-                        .expect("Invalid Wavefront Obj: Texture Vertex coordinate isn't a float")
-                    }
+                        .map_err(invalid)
+                    };
 
                     Vector2 {
-                        x: vt_parse(data.next()),
-                        y: vt_parse(data.next()),
+                        x: vt_parse()?,
+                        y: vt_parse()?,
                     }
                 }),
                 Some("vn") => model.normals.push({
-                    /// Function to reduce repeated code
-                    fn vn_parse(data: Option<&str>) -> f64 {
-                        data.expect(
-                            "Invalid Wavefront Obj: Normal have less than three coordinates",
+                    let mut vn_parse = || {
+                        parse::<f64>(
+                            data.next(),
+                            "Normal have less than three coordinates",
+                            "Normal coordinate isn't a float",
                         )
-                        .trim()
-                        .parse::<f64>()
-                        .expect("Invalid Wavefront Obj: Normal coordinate isn't a float")
-                    }
+                        .map_err(invalid)
+                    };
 
                     Vector3 {
-                        x: vn_parse(data.next()),
-                        y: vn_parse(data.next()),
-                        z: vn_parse(data.next()),
+                        x: vn_parse()?,
+                        y: vn_parse()?,
+                        z: vn_parse()?,
                     }
                     .normalize()
                 }),
                 Some("f") => {
                     let mut face: Vec<Element> = Vec::new();
                     for element in data {
-                        let mut element = element.split("/").filter(|string| !string.is_empty());
-                        let vertex_index = element
-                            .next()
-                            .expect("Invalid Wavefront Obj: no face vertex index")
-                            .trim()
-                            .parse::<isize>()
-                            .expect(
-                                "Invalid Wavefront Obj: The face vertex index isn't an integer",
-                            );
+                        let mut element = element.split("/");
+                        let vertex_index = match element.next() {
+                            Some(string) if !string.is_empty() => {
+                                index_parse(string, model.vertices.len(), "vertex")
+                                    .map_err(invalid)?
+                            }
+                            _ => {
+                                return Err(invalid(
+                                    "Invalid Wavefront Obj: no face vertex index".to_string(),
+                                ))
+                            }
+                        };
 
                         let texture_index = match element.next() {
-                            Some(string) => {
-                                Some(string
-                                    .trim()
-                                    .parse::<isize>()
-                                    .expect(
-                                        "Invalid Wavefront Obj: The face texture vertex index isn't an integer"
-                                    )
-                                )
-                            }
-                            None => None,
+                            Some(string) if !string.is_empty() => Some(
+                                index_parse(string, model.textures.len(), "texture vertex")
+                                    .map_err(invalid)?,
+                            ),
+                            _ => None,
                         };
 
                         let normal_index = match element.next() {
-                            Some(string) => {
-                                Some(string
-                                    .trim()
-                                    .parse::<isize>()
-                                    .expect(
-                                        "Invalid Wavefront Obj: The face normal vertex index isn't an integer"
-                                    )
-                                )
-                            }
-                            None => {
+                            Some(string) if !string.is_empty() => Some(
+                                index_parse(string, model.normals.len(), "normal vertex")
+                                    .map_err(invalid)?,
+                            ),
+                            _ => {
                                 no_computed_normals = true;
                                 None
-                            },
+                            }
                         };
 
                         face.push((vertex_index, texture_index, normal_index));
                     }
-                    model.faces.push(face);
+
+                    if face.len() < 3 {
+                        return Err(invalid(
+                            "Invalid Wavefront Obj: The face have less than three vertices"
+                                .to_string(),
+                        ));
+                    }
+
+                    for triangle in triangulate(&face, &model.vertices) {
+                        model.faces.push(Face {
+                            elements: triangle.iter().map(|&i| face[i]).collect(),
+                            outline: outline(triangle, face.len()),
+                            material,
+                            object,
+                            group,
+                            smoothing,
+                        });
+                    }
+                }
+                Some("o") => {
+                    let name = data.collect::<Vec<&str>>().join(" ");
+                    model.objects.push(name);
+                    object = model.objects.len() - 1;
+                }
+                Some("g") => {
+                    model.groups.push(data.map(String::from).collect());
+                    group = model.groups.len() - 1;
+                }
+                Some("s") => {
+                    smoothing = match data.next() {
+                        Some("off") | None => 0,
+                        value => parse::<u32>(value, "", "The smoothing group isn't an integer")
+                            .map_err(invalid)?,
+                    };
+                }
+                Some("mtllib") => {
+                    for name in data {
+                        let path = directory.join(name);
+                        let materials = Material::load_mtl(&path.to_string_lossy())?;
+                        model.materials.extend(materials);
+                    }
+                }
+                Some("usemtl") => {
+                    let name = data.next().unwrap_or("");
+                    // The last material with the name, or the default
+                    material = model
+                        .materials
+                        .iter()
+                        .rposition(|material| material.name == name && !name.is_empty())
+                        .unwrap_or(0);
                 }
                 _ => continue,
             }
@@ -515,11 +751,56 @@ impl Model {
             model.compute_normals();
         }
 
+        if !model.textures.is_empty() {
+            model.compute_tangents();
+        }
+
         Ok(model)
     }
 
-    /// Iterator of faces that is (vertex, Option<texture_vertex>, normal)
-    pub fn faces(&self) -> FaceIterator {
+    /// Names of the objects (o) in the model
+    pub fn objects(&self) -> Vec<&str> {
+        self.objects
+            .iter()
+            .filter(|name| !name.is_empty())
+            .map(String::as_str)
+            .collect()
+    }
+
+    /// Names of the groups (g) in the model
+    pub fn groups(&self) -> Vec<&str> {
+        let mut groups: Vec<&str> = Vec::new();
+        for name in self.groups.iter().flatten() {
+            if !groups.contains(&name.as_str()) {
+                groups.push(name);
+            }
+        }
+        groups
+    }
+
+    /// Show or hide the faces of the object or the group with the name
+    ///
+    /// A face is hidden if its object or one of its groups is hidden
+    #[allow(dead_code)]
+    pub fn set_visible(&mut self, name: &str, visible: bool) {
+        if visible {
+            self.hidden.remove(name);
+        } else {
+            self.hidden.insert(name.to_string());
+        }
+    }
+
+    /// Test if the face isn't hidden
+    fn is_visible(&self, face: &Face) -> bool {
+        self.hidden.is_empty()
+            || !(self.hidden.contains(&self.objects[face.object])
+                || self.groups[face.group]
+                    .iter()
+                    .any(|name| self.hidden.contains(name)))
+    }
+
+    /// Iterator of visible faces that is a list of vertices
+    pub fn faces(&self) -> FaceIterator<'_> {
         FaceIterator {
             model: self,
             index: 0,
@@ -533,27 +814,37 @@ pub struct FaceIterator<'a> {
 }
 
 impl<'a> Iterator for FaceIterator<'a> {
-    type Item = Vec<(Vector3, Option<Vector2>, Vector3)>;
+    type Item = Vec<Vertex>;
     fn next(&mut self) -> Option<Self::Item> {
-        if self.index >= self.model.faces.len() {
+        let model = self.model;
+
+        // Skip the hidden faces
+        while self.index < model.faces.len() && !model.is_visible(&model.faces[self.index]) {
+            self.index += 1;
+        }
+
+        if self.index >= model.faces.len() {
             return None;
         }
 
-        let model = self.model;
         let face = &model.faces[self.index];
         let mut result: Self::Item = Vec::new();
 
-        for element in face {
+        for element in &face.elements {
             let &(vi, vti, vni) = element;
 
-            let v = self.model.vertices[convert_index(vi, model.vertices.len())];
-            let vt = match vti {
-                Some(vti) => Some(model.textures[convert_index(vti, model.textures.len())]),
-                None => None,
-            };
-            let vn = model.normals[convert_index(vni.unwrap(), model.normals.len())];
-
-            result.push((v, vt, vn));
+            let v = model.vertices[vi];
+            let vt = vti.map(|vti| model.textures[vti]);
+            let vn = model.normals[vni.unwrap()];
+            let tangent = vt.and_then(|_| model.tangents.get(vi).copied());
+
+            result.push(Vertex {
+                position: v,
+                texture: vt,
+                normal: vn,
+                tangent,
+                material: face.material,
+            });
         }
 
         self.index += 1;
@@ -561,106 +852,190 @@ impl<'a> Iterator for FaceIterator<'a> {
     }
 }
 
-/// Matrix that deform the coordinate to make a perspective projection
-fn matrix_perspective(camera_z: f64) -> Matrix {
-    mat![4, 4 =>
-        1.0, 0.0, 0.0,           0.0;
-        0.0, 1.0, 0.0,           0.0;
-        0.0, 0.0, 1.0,           0.0;
-        0.0, 0.0, -1.0/camera_z, 1.0;
-    ]
+/// Convert a point in the model to a point in the image
+/// using the transform to the screen
+///
+/// Return None if the point is behind the eye
+fn screen_point(transform: &Matrix4, point: Vector3) -> Option<Point> {
+    screen_position(transform, point).map(|v| Point {
+        x: v.x as i32,
+        y: v.y as i32,
+    })
 }
 
-/// Matrix that change the size and the position of the model
-///
-/// The model is mapped onto scree cube
-/// [position.x, position.x+size.x] * [position.y, position.y+size.y] * [position.z, position.z+size.z]
-fn matrix_viewport(position: Vector3, size: Vector3) -> Matrix {
-    let Vector3 { x, y, z } = position;
-    // w = width, h = height, d = depth
-    let Vector3 { x: w, y: h, z: d } = size;
-
-    mat![4, 4 =>
-        w / 2.0, 0.0,     0.0,   x + w / 2.0;
-        0.0,     h / 2.0, 0.0,   y + h / 2.0;
-        0.0,     0.0,     d/2.0, z + d / 2.0;
-        0.0,     0.0,     0.0,   1.0        ;
-    ]
+/// Like screen_point but without rounding the position to a pixel
+fn screen_position(transform: &Matrix4, point: Vector3) -> Option<Vector2> {
+    let v = *transform * point.to_vector4(true);
+    if v.w > 0.0 {
+        let v = v.to_vector3();
+        Some(Vector2 { x: v.x, y: v.y })
+    } else {
+        None
+    }
 }
 
-/// Matrix that convert the coordinate to the frame (center, i', j' k')
-/// Where eye is a point and the camera is in eye pointing to the center
-/// and the vector up is in vertical
+/// Split a polygon face in triangles with the same winding order,
+/// the triangles are the indexes of their vertices in the polygon
 ///
-/// eye is a point, center is a point, up is a vector
-fn matrix_model_view(eye: Vector3, center: Vector3, up: Vector3) -> Matrix {
-    // The problem is:
-    // The origin of the new frame is the point C (center)
-    // the point E (eye) is in the z-axis of the frame and
-    // the vector u (up) has the x coordinate equals to zero
-    // because it's in vertical
-
-    // We want a matrix M that v' = v * M where v is in standard basis
-    // and v' is in the basis (i', j', k')
-    //
-    // We know that
-    // | 1 |           | 0 |           | 0 |
-    // | 0 | = M * i', | 1 | = M * j', | 0 | = M * k'
-    // | 0 |           | 0 |           | 1 |
-    //
-    // Then M (that is unique because it convert basis) is:
-    // | i'x  i'y  i'z |
-    // | j'x  j'y  j'z |
-    // | k'x  k'y  k'z |
-    //
-    // For example, i' * M =
-    // | i'x  i'y  i'z | | i'x |   | ||i'||  |   | 1 |
-    // | j'x  j'y  j'z | | i'y | = | i' * j' | = | 0 |
-    // | k'x  k'y  k'z | | i'z |   | i' * k' |   | 0 |
-    //
-    // To convert the coordinates of the point P in the frame (O, i, j, k)
-    // to P' in the frame (C, i', j', k')
-    // first we move c to the origin O then we multiply by M
-    // P' = M * (P - C)
-    //
-    // It's the same that
-    // | P'x*r |   | i'x  i'y  i'z  0 | | 1  0  0  -cx |
-    // | P'y*r | = | j'x  j'y  j'z  0 | | 0  1  0  -cy |
-    // | P'z*r |   | k'x  k'y  k'z  0 | | 0  0  1  -cz |
-    // |   r   |   |  0    0    0   1 | | 0  0  0   1  |
-
-    // k' = CE / || CE ||
-    // i' = (u ^ k') / ||u ^ k'||
-    // j' = k' ^ i'
-
-    let k_ = (eye - center).normalize();
-    let i_ = up.cross(k_).normalize();
-    let j_ = k_.cross(i_).normalize(); // Don't need to be normalized
-
-    let m = mat![4, 4=>
-        i_.x, i_.y, i_.z, 0.0;
-        j_.x, j_.y, j_.z, 0.0;
-        k_.x, k_.y, k_.z, 0.0;
-        0.0,  0.0,  0.0,  1.0;
-    ];
-
-    let t = mat![4, 4 =>
-        1.0, 0.0, 0.0, -center.x;
-        0.0, 1.0, 0.0, -center.y;
-        0.0, 0.0, 1.0, -center.z;
-        0.0, 0.0, 0.0, 1.0;
-    ];
-
-    return m * t; // ModelView
+/// A convex polygon is split in a fan around the first vertex
+/// and a concave polygon is split by ear clipping
+fn triangulate(polygon: &[Element], vertices: &[Vector3]) -> Vec<[usize; 3]> {
+    if polygon.len() <= 3 {
+        return vec![[0, 1, 2]];
+    }
+
+    let position = |i: &usize| vertices[polygon[*i].0];
+    let polygon: Vec<usize> = (0..polygon.len()).collect();
+
+    // Normal of the polygon using the Newell's method
+    // (it works with concave polygons too)
+    let mut normal = Vector3 {
+        x: 0.0,
+        y: 0.0,
+        z: 0.0,
+    };
+    for (i, element) in polygon.iter().enumerate() {
+        let next = &polygon[(i + 1) % polygon.len()];
+        normal = normal + position(element).cross(position(next));
+    }
+
+    // Test if the corner a b c turns in the same direction of the polygon
+    let convex = |a: Vector3, b: Vector3, c: Vector3| (b - a).cross(c - b) * normal > 0.0;
+
+    // Test if p is inside the triangle a b c (or on its edges)
+    let inside = |p: Vector3, a: Vector3, b: Vector3, c: Vector3| {
+        (b - a).cross(p - a) * normal >= 0.0
+            && (c - b).cross(p - b) * normal >= 0.0
+            && (a - c).cross(p - c) * normal >= 0.0
+    };
+
+    let n = polygon.len();
+    let is_convex = (0..n).all(|i| {
+        convex(
+            position(&polygon[i]),
+            position(&polygon[(i + 1) % n]),
+            position(&polygon[(i + 2) % n]),
+        )
+    });
+    if is_convex {
+        return (1..n - 1).map(|i| [0, i, i + 1]).collect();
+    }
+
+    // Ear clipping: remove a convex corner without other vertices inside
+    // until only a triangle remains
+    let mut polygon = polygon;
+    let mut triangles: Vec<[usize; 3]> = Vec::new();
+    while polygon.len() > 3 {
+        let n = polygon.len();
+        let corner = |i: usize| ((i + n - 1) % n, i, (i + 1) % n);
+
+        let ear = (0..n)
+            .find(|&i| {
+                let (prev, _, next) = corner(i);
+                let a = position(&polygon[prev]);
+                let b = position(&polygon[i]);
+                let c = position(&polygon[next]);
+
+                convex(a, b, c)
+                    && polygon.iter().enumerate().all(|(j, element)| {
+                        j == prev || j == i || j == next || !inside(position(element), a, b, c)
+                    })
+            })
+            // A degenerate polygon has no ear, so clip any corner
+            .unwrap_or(0);
+
+        let (prev, _, next) = corner(ear);
+        triangles.push([polygon[prev], polygon[ear], polygon[next]]);
+        polygon.remove(ear);
+    }
+    triangles.push([polygon[0], polygon[1], polygon[2]]);
+
+    triangles
+}
+
+/// Edges of the triangle of the triangulation of a polygon with n vertices
+/// that are edges of the polygon (they join consecutive vertices)
+fn outline(triangle: [usize; 3], n: usize) -> [bool; 3] {
+    std::array::from_fn(|i| {
+        let (a, b) = (triangle[i], triangle[(i + 1) % 3]);
+        (a + 1) % n == b || (b + 1) % n == a
+    })
 }
 
 /// Convert a isize 1-based index into a usize 0-based index
 ///
 /// The input can be negative with -1 meaning the last, -2 meaning the last but one, ...
-fn convert_index(index: isize, max: usize) -> usize {
-    if index >= 0 {
-        (index - 1) as usize
+///
+/// Return None if the index is out of the range of the max elements
+fn convert_index(index: isize, max: usize) -> Option<usize> {
+    let index = if index > 0 {
+        index - 1
     } else {
-        ((max as isize) + index) as usize
+        (max as isize) + index
+    };
+
+    if index >= 0 && (index as usize) < max {
+        Some(index as usize)
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Triangulate the polygon in the plane z = 0 and test
+    /// that the triangles cover the polygon with its winding order
+    /// and that only the edges of the polygon are in the outline
+    fn assert_triangulation(points: &[(f64, f64)]) {
+        let vertices: Vec<Vector3> = points
+            .iter()
+            .map(|&(x, y)| Vector3 { x, y, z: 0.0 })
+            .collect();
+        let polygon: Vec<Element> = (0..points.len()).map(|i| (i, None, None)).collect();
+        let triangles = triangulate(&polygon, &vertices);
+        assert_eq!(triangles.len(), points.len() - 2);
+
+        // Signed area of the polygon with the vertices
+        let area = |indexes: &[usize]| -> f64 {
+            let n = indexes.len();
+            (0..n)
+                .map(|i| {
+                    let (p, q) = (points[indexes[i]], points[indexes[(i + 1) % n]]);
+                    (p.0 * q.1 - q.0 * p.1) / 2.0
+                })
+                .sum()
+        };
+        assert!(triangles.iter().all(|triangle| area(triangle) > 0.0));
+        let sum: f64 = triangles.iter().map(|triangle| area(triangle)).sum();
+        let all: Vec<usize> = (0..points.len()).collect();
+        assert!((sum - area(&all)).abs() < 1e-9);
+
+        let edges: usize = triangles
+            .iter()
+            .map(|&triangle| outline(triangle, points.len()))
+            .map(|outline| outline.iter().filter(|&&edge| edge).count())
+            .sum();
+        assert_eq!(edges, points.len());
+    }
+
+    #[test]
+    fn triangulate_convex() {
+        assert_triangulation(&[(0.0, 0.0), (1.0, 0.0), (1.0, 1.0), (0.0, 1.0)]);
+        assert_triangulation(&[(0.0, 0.0), (2.0, 0.0), (3.0, 1.0), (1.5, 2.0), (0.0, 1.0)]);
+    }
+
+    #[test]
+    fn triangulate_concave() {
+        // An L
+        assert_triangulation(&[
+            (0.0, 0.0),
+            (2.0, 0.0),
+            (2.0, 1.0),
+            (1.0, 1.0),
+            (1.0, 2.0),
+            (0.0, 2.0),
+        ]);
     }
 }
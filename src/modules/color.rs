@@ -1,13 +1,21 @@
-#[derive(Copy, Clone, Debug, PartialEq)]
+use std::ops::{Add, Mul};
+
+/// An 8-bit RGBA color
+///
+/// The alpha is the opacity, 255 is opaque and 0 is transparent
+/// (the default color is a transparent black)
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
 pub struct Color {
     pub r: u8,
     pub g: u8,
     pub b: u8,
+    pub a: u8,
 }
 
 impl Color {
     /// Receive a hexadecimal color value and return a Color
-    /// The value must be follow one of this formats: #HHH or #HHHHHH
+    /// The value must be follow one of this formats: #HHH, #HHHH, #HHHHHH or #HHHHHHHH
+    /// (the last digits are the alpha, it's opaque without them)
     /// If it is a invalid value, return black
     pub const fn hex(value: &[u8]) -> Self {
         /// Convert a u8 ascii char to the hexadecimal equivalent
@@ -24,63 +32,98 @@ impl Color {
             }
         }
 
-        match value {
-            &[b'#', r, g, b] => {
-                let r = char_to_hex(r);
-                let g = char_to_hex(g);
-                let b = char_to_hex(b);
-                Self {
-                    r: (r << 4) + r,
-                    g: (g << 4) + g,
-                    b: (b << 4) + b,
-                }
+        /// Convert one hexadecimal digit to a channel (#H = #HH)
+        const fn short(val: u8) -> u8 {
+            let h = char_to_hex(val);
+            (h << 4) + h
+        }
+
+        /// Convert two hexadecimal digits to a channel
+        const fn long(h1: u8, h0: u8) -> u8 {
+            (char_to_hex(h1) << 4) + char_to_hex(h0)
+        }
+
+        match *value {
+            [b'#', r, g, b] => Self::rgba(short(r), short(g), short(b), 255),
+            [b'#', r, g, b, a] => Self::rgba(short(r), short(g), short(b), short(a)),
+            [b'#', r1, r0, g1, g0, b1, b0] => {
+                Self::rgba(long(r1, r0), long(g1, g0), long(b1, b0), 255)
             }
-            &[b'#', r1, r0, g1, g0, b1, b0] => {
-                let r1 = char_to_hex(r1);
-                let r0 = char_to_hex(r0);
-                let g1 = char_to_hex(g1);
-                let g0 = char_to_hex(g0);
-                let b1 = char_to_hex(b1);
-                let b0 = char_to_hex(b0);
-                Self {
-                    r: (r1 << 4) + r0,
-                    g: (g1 << 4) + g0,
-                    b: (b1 << 4) + b0,
-                }
+            [b'#', r1, r0, g1, g0, b1, b0, a1, a0] => {
+                Self::rgba(long(r1, r0), long(g1, g0), long(b1, b0), long(a1, a0))
             }
-            _ => Self { r: 0, g: 0, b: 0 },
+            _ => Self::rgba(0, 0, 0, 255),
         }
     }
 
+    pub const fn rgba(r: u8, g: u8, b: u8, a: u8) -> Self {
+        Self { r, g, b, a }
+    }
+
+    /// Opaque gray
     pub const fn gray(value: u8) -> Self {
-        Self {
-            r: value,
-            g: value,
-            b: value,
-        }
+        Self::rgba(value, value, value, 255)
+    }
+
+    /// Return the color with other alpha
+    pub const fn with_alpha(self, a: u8) -> Self {
+        Self { a, ..self }
     }
 
-    /// Return a color with the intensity changed
+    /// Return a color with the intensity changed,
+    /// the alpha isn't changed
     pub fn light(self, intensity: f64) -> Self {
         if intensity > 0.0 {
             Self {
                 r: ((self.r as f64) * intensity) as u8,
                 g: ((self.g as f64) * intensity) as u8,
                 b: ((self.b as f64) * intensity) as u8,
+                a: self.a,
             }
         } else {
-            Self::hex(b"#000")
+            Self::hex(b"#000").with_alpha(self.a)
         }
     }
 
-    /// Convert color to bytes
-    pub fn to_bytes(self) -> [u8; 3] {
-        [self.b, self.g, self.r]
+    /// Convert color to bytes (BGRA like in the image files)
+    pub fn to_bytes(self) -> [u8; 4] {
+        [self.b, self.g, self.r, self.a]
     }
 
-    /// Convert bytes to color
-    pub fn from_bytes(buffer: [u8; 3]) -> Self {
-        let [b, g, r] = buffer;
-        Self { r, g, b }
+    /// Convert bytes (BGRA like in the image files) to color
+    pub fn from_bytes(buffer: [u8; 4]) -> Self {
+        let [b, g, r, a] = buffer;
+        Self { r, g, b, a }
+    }
+}
+
+/// Multiply each channel, used to filter a color with other
+///
+/// (r0, g0, b0) * (r1, g1, b1) = (r0 * r1 / 255, g0 * g1 / 255, b0 * b1 / 255)
+impl Mul for Color {
+    type Output = Self;
+    fn mul(self, other: Self) -> Self::Output {
+        let mul = |a: u8, b: u8| ((a as u16) * (b as u16) / 255) as u8;
+        Self {
+            r: mul(self.r, other.r),
+            g: mul(self.g, other.g),
+            b: mul(self.b, other.b),
+            a: mul(self.a, other.a),
+        }
+    }
+}
+
+/// Sum each channel, used to sum lights
+///
+/// The channels saturate at 255
+impl Add for Color {
+    type Output = Self;
+    fn add(self, other: Self) -> Self::Output {
+        Self {
+            r: self.r.saturating_add(other.r),
+            g: self.g.saturating_add(other.g),
+            b: self.b.saturating_add(other.b),
+            a: self.a.saturating_add(other.a),
+        }
     }
 }
@@ -0,0 +1,97 @@
+use std::ops::{Add, Mul};
+
+use super::Color;
+
+/// Gamma of the 8-bit colors of the textures and the materials
+pub const GAMMA: f32 = 2.2;
+
+/// A color with floating point channels used to sum lights
+/// without clipping or truncating them
+///
+/// The channels are linear, 1 is the brightest 8-bit color
+/// and they can be bigger (high dynamic range)
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct HdrColor {
+    pub r: f32,
+    pub g: f32,
+    pub b: f32,
+}
+
+impl HdrColor {
+    pub const fn gray(value: f32) -> Self {
+        Self {
+            r: value,
+            g: value,
+            b: value,
+        }
+    }
+
+    /// Convert an 8-bit color with gamma to a linear color
+    pub fn from_srgb(color: Color) -> Self {
+        let decode = |c: u8| ((c as f32) / 255.0).powf(GAMMA);
+        Self {
+            r: decode(color.r),
+            g: decode(color.g),
+            b: decode(color.b),
+        }
+    }
+
+    /// Convert to an opaque 8-bit color,
+    /// the channels are clamped to [0, 1]
+    pub fn to_color(self) -> Color {
+        let encode = |c: f32| (c.clamp(0.0, 1.0) * 255.0).round() as u8;
+        Color::rgba(encode(self.r), encode(self.g), encode(self.b), 255)
+    }
+
+    /// Apply the function to each channel
+    pub fn map(self, f: impl Fn(f32) -> f32) -> Self {
+        Self {
+            r: f(self.r),
+            g: f(self.g),
+            b: f(self.b),
+        }
+    }
+}
+
+/// Convert an 8-bit color without gamma (c / 255)
+impl From<Color> for HdrColor {
+    fn from(color: Color) -> Self {
+        Self {
+            r: (color.r as f32) / 255.0,
+            g: (color.g as f32) / 255.0,
+            b: (color.b as f32) / 255.0,
+        }
+    }
+}
+
+/// Multiply each channel, used to filter a color with other
+impl Mul for HdrColor {
+    type Output = Self;
+    fn mul(self, other: Self) -> Self::Output {
+        Self {
+            r: self.r * other.r,
+            g: self.g * other.g,
+            b: self.b * other.b,
+        }
+    }
+}
+
+/// Change the intensity of the color
+impl Mul<f32> for HdrColor {
+    type Output = Self;
+    fn mul(self, other: f32) -> Self::Output {
+        self.map(|c| c * other)
+    }
+}
+
+/// Sum each channel, used to sum lights
+impl Add for HdrColor {
+    type Output = Self;
+    fn add(self, other: Self) -> Self::Output {
+        Self {
+            r: self.r + other.r,
+            g: self.g + other.g,
+            b: self.b + other.b,
+        }
+    }
+}
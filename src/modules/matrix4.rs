@@ -0,0 +1,308 @@
+use std::ops::Mul;
+
+use super::{Quaternion, Vector3, Vector4};
+
+/// A matrix 4x4 that transforms points and vectors in homogeneous coordinates
+///
+/// It's stored in the stack by rows,
+/// aligned so each row fits in one AVX register
+#[derive(Copy, Clone, Debug)]
+#[repr(C, align(32))]
+pub struct Matrix4 {
+    rows: [[f64; 4]; 4],
+}
+
+impl Matrix4 {
+    /// Create a matrix with the rows
+    ///
+    /// ```
+    /// Matrix4::new([
+    ///     [1.0, 0.0, 0.0, 2.0],
+    ///     [0.0, 1.0, 0.0, 3.0],
+    ///     [0.0, 0.0, 1.0, 4.0],
+    ///     [0.0, 0.0, 0.0, 1.0],
+    /// ])
+    /// ```
+    pub fn new(rows: [[f64; 4]; 4]) -> Self {
+        Self { rows }
+    }
+
+    pub fn identity() -> Self {
+        Self::scale(Vector3 {
+            x: 1.0,
+            y: 1.0,
+            z: 1.0,
+        })
+    }
+
+    /// Matrix that move the model by t
+    pub fn translation(t: Vector3) -> Self {
+        Self::new([
+            [1.0, 0.0, 0.0, t.x],
+            [0.0, 1.0, 0.0, t.y],
+            [0.0, 0.0, 1.0, t.z],
+            [0.0, 0.0, 0.0, 1.0],
+        ])
+    }
+
+    /// Matrix that rotate the model by angle radians around the axis
+    /// (counterclockwise looking from the axis to the origin)
+    pub fn rotation(axis: Vector3, angle: f64) -> Self {
+        // Rodrigues' rotation formula
+        let Vector3 { x, y, z } = axis.normalize();
+        let (s, c) = angle.sin_cos();
+        let t = 1.0 - c;
+
+        #[rustfmt::skip]
+        let rows = [
+            [t * x * x + c,     t * x * y - s * z, t * x * z + s * y, 0.0],
+            [t * x * y + s * z, t * y * y + c,     t * y * z - s * x, 0.0],
+            [t * x * z - s * y, t * y * z + s * x, t * z * z + c,     0.0],
+            [0.0,               0.0,               0.0,               1.0],
+        ];
+        Self::new(rows)
+    }
+
+    /// Matrix that scale the model by s.x, s.y and s.z in each axis
+    pub fn scale(s: Vector3) -> Self {
+        Self::new([
+            [s.x, 0.0, 0.0, 0.0],
+            [0.0, s.y, 0.0, 0.0],
+            [0.0, 0.0, s.z, 0.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ])
+    }
+
+    /// Matrix that rotate the model like the quaternion
+    ///
+    /// The quaternion is normalized
+    #[allow(dead_code)]
+    pub fn from_quaternion(q: Quaternion) -> Self {
+        let Quaternion { w, x, y, z } = q.normalize();
+
+        #[rustfmt::skip]
+        let rows = [
+            [1.0 - 2.0 * (y * y + z * z), 2.0 * (x * y - w * z),       2.0 * (x * z + w * y),       0.0],
+            [2.0 * (x * y + w * z),       1.0 - 2.0 * (x * x + z * z), 2.0 * (y * z - w * x),       0.0],
+            [2.0 * (x * z - w * y),       2.0 * (y * z + w * x),       1.0 - 2.0 * (x * x + y * y), 0.0],
+            [0.0,                         0.0,                         0.0,                         1.0],
+        ];
+        Self::new(rows)
+    }
+
+    /// Quaternion with the rotation of the linear part of the matrix
+    ///
+    /// The linear part must be a rotation (without scale)
+    #[allow(dead_code)]
+    pub fn to_quaternion(self) -> Quaternion {
+        let m = |i: usize, j: usize| self.rows[i][j];
+        let trace = m(0, 0) + m(1, 1) + m(2, 2);
+
+        // Divide by the biggest component to not lose precision
+        let q = if trace > 0.0 {
+            let s = (trace + 1.0).sqrt() * 2.0;
+            Quaternion {
+                w: s / 4.0,
+                x: (m(2, 1) - m(1, 2)) / s,
+                y: (m(0, 2) - m(2, 0)) / s,
+                z: (m(1, 0) - m(0, 1)) / s,
+            }
+        } else if m(0, 0) > m(1, 1) && m(0, 0) > m(2, 2) {
+            let s = (1.0 + m(0, 0) - m(1, 1) - m(2, 2)).sqrt() * 2.0;
+            Quaternion {
+                w: (m(2, 1) - m(1, 2)) / s,
+                x: s / 4.0,
+                y: (m(0, 1) + m(1, 0)) / s,
+                z: (m(0, 2) + m(2, 0)) / s,
+            }
+        } else if m(1, 1) > m(2, 2) {
+            let s = (1.0 + m(1, 1) - m(0, 0) - m(2, 2)).sqrt() * 2.0;
+            Quaternion {
+                w: (m(0, 2) - m(2, 0)) / s,
+                x: (m(0, 1) + m(1, 0)) / s,
+                y: s / 4.0,
+                z: (m(1, 2) + m(2, 1)) / s,
+            }
+        } else {
+            let s = (1.0 + m(2, 2) - m(0, 0) - m(1, 1)).sqrt() * 2.0;
+            Quaternion {
+                w: (m(1, 0) - m(0, 1)) / s,
+                x: (m(0, 2) + m(2, 0)) / s,
+                y: (m(1, 2) + m(2, 1)) / s,
+                z: s / 4.0,
+            }
+        };
+        q.normalize()
+    }
+
+    pub fn get(&self, i: usize, j: usize) -> f64 {
+        self.rows[i][j]
+    }
+
+    pub fn transpose(self) -> Self {
+        Self::new(std::array::from_fn(|i| self.rows.map(|row| row[i])))
+    }
+
+    /// Determinants of the 2x2 submatrices of the 2 first rows
+    /// and of the 2 last rows, used by the determinant and the inverse
+    ///
+    /// They are in the order of the columns (0, 1), (0, 2), (0, 3),
+    /// (1, 2), (1, 3), (2, 3)
+    fn minors(&self) -> ([f64; 6], [f64; 6]) {
+        let pairs = [(0, 1), (0, 2), (0, 3), (1, 2), (1, 3), (2, 3)];
+        let minor = |i: usize, k: usize, (j, l): (usize, usize)| {
+            self.rows[i][j] * self.rows[k][l] - self.rows[k][j] * self.rows[i][l]
+        };
+        (pairs.map(|p| minor(0, 1, p)), pairs.map(|p| minor(2, 3, p)))
+    }
+
+    #[allow(dead_code)]
+    pub fn determinant(&self) -> f64 {
+        // Laplace expansion by the 2 first rows
+        let (s, c) = self.minors();
+        s[0] * c[5] - s[1] * c[4] + s[2] * c[3] + s[3] * c[2] - s[4] * c[1] + s[5] * c[0]
+    }
+
+    /// Inverse of the matrix, or None if the matrix isn't invertible
+    #[allow(dead_code)]
+    pub fn inverse(&self) -> Option<Self> {
+        // The determinant is compared with the biggest that it can be
+        // for the size of the rows (Hadamard's inequality),
+        // so the scale of the matrix doesn't matter
+        let determinant = self.determinant();
+        let norms: f64 = self
+            .rows
+            .iter()
+            .map(|row| row.iter().map(|v| v * v).sum::<f64>().sqrt())
+            .product();
+        if determinant.abs() <= norms * f64::EPSILON {
+            return None;
+        }
+
+        // The adjugate matrix (the transpose of the cofactor matrix)
+        // divided by the determinant
+        let (s, c) = self.minors();
+        let a = self.rows;
+        #[rustfmt::skip]
+        let adjugate = [
+            [
+                a[1][1] * c[5] - a[1][2] * c[4] + a[1][3] * c[3],
+                -a[0][1] * c[5] + a[0][2] * c[4] - a[0][3] * c[3],
+                a[3][1] * s[5] - a[3][2] * s[4] + a[3][3] * s[3],
+                -a[2][1] * s[5] + a[2][2] * s[4] - a[2][3] * s[3],
+            ],
+            [
+                -a[1][0] * c[5] + a[1][2] * c[2] - a[1][3] * c[1],
+                a[0][0] * c[5] - a[0][2] * c[2] + a[0][3] * c[1],
+                -a[3][0] * s[5] + a[3][2] * s[2] - a[3][3] * s[1],
+                a[2][0] * s[5] - a[2][2] * s[2] + a[2][3] * s[1],
+            ],
+            [
+                a[1][0] * c[4] - a[1][1] * c[2] + a[1][3] * c[0],
+                -a[0][0] * c[4] + a[0][1] * c[2] - a[0][3] * c[0],
+                a[3][0] * s[4] - a[3][1] * s[2] + a[3][3] * s[0],
+                -a[2][0] * s[4] + a[2][1] * s[2] - a[2][3] * s[0],
+            ],
+            [
+                -a[1][0] * c[3] + a[1][1] * c[1] - a[1][2] * c[0],
+                a[0][0] * c[3] - a[0][1] * c[1] + a[0][2] * c[0],
+                -a[3][0] * s[3] + a[3][1] * s[1] - a[3][2] * s[0],
+                a[2][0] * s[3] - a[2][1] * s[1] + a[2][2] * s[0],
+            ],
+        ];
+        Some(Self::new(adjugate.map(|row| row.map(|v| v / determinant))))
+    }
+}
+
+/// Dot product of a row and a column
+#[inline(always)]
+fn dot(row: [f64; 4], column: [f64; 4]) -> f64 {
+    row.iter().zip(column).fold(0.0, |sum, (a, b)| sum + a * b)
+}
+
+impl Mul for Matrix4 {
+    type Output = Self;
+    fn mul(self, other: Self) -> Self::Output {
+        let other = other.transpose();
+        Self::new(
+            self.rows
+                .map(|row| other.rows.map(|column| dot(row, column))),
+        )
+    }
+}
+
+impl Mul<Vector4> for Matrix4 {
+    type Output = Vector4;
+    fn mul(self, v: Vector4) -> Self::Output {
+        let v = v.to_array();
+        let [x, y, z, w] = self.rows.map(|row| dot(row, v));
+        Vector4 { x, y, z, w }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_near(a: Matrix4, b: Matrix4, tolerance: f64) {
+        for i in 0..4 {
+            for j in 0..4 {
+                let (x, y) = (a.get(i, j), b.get(i, j));
+                assert!((x - y).abs() <= tolerance, "{:?} != {:?}", a, b);
+            }
+        }
+    }
+
+    fn vector(x: f64, y: f64, z: f64) -> Vector3 {
+        Vector3 { x, y, z }
+    }
+
+    #[test]
+    fn inverse() {
+        let m = Matrix4::translation(vector(1.0, 2.0, -3.0))
+            * Matrix4::rotation(vector(0.3, -1.0, 0.5), 0.7)
+            * Matrix4::scale(vector(2.0, 0.5, 3.0));
+        assert!((m.determinant() - 3.0).abs() < 1e-12);
+        assert_near(m * m.inverse().unwrap(), Matrix4::identity(), 1e-12);
+        assert_near(m.inverse().unwrap() * m, Matrix4::identity(), 1e-12);
+
+        // A small scale is invertible
+        let small = Matrix4::scale(vector(1e-6, 1e-6, 1e-6));
+        let inverse = small.inverse().unwrap();
+        assert_near(inverse, Matrix4::scale(vector(1e6, 1e6, 1e6)), 1e-6);
+
+        let flat = Matrix4::scale(vector(1.0, 0.0, 1.0));
+        assert!(flat.inverse().is_none());
+    }
+
+    #[test]
+    fn quaternion() {
+        let axes = [
+            vector(1.0, 0.0, 0.0),
+            vector(0.0, 1.0, 0.0),
+            vector(0.0, 0.0, 1.0),
+            vector(0.3, -1.0, 0.5),
+        ];
+        let pi = std::f64::consts::PI;
+        for axis in axes {
+            for angle in [0.0, 0.5, 2.0, pi - 1e-6, pi, -pi + 1e-6] {
+                let m = Matrix4::rotation(axis, angle);
+                let q = Quaternion::from_axis_angle(axis, angle);
+                assert_near(Matrix4::from_quaternion(q), m, 1e-12);
+
+                // q and -q are the same rotation
+                let r = m.to_quaternion();
+                let difference = |sign: f64| {
+                    let Quaternion { w, x, y, z } = q;
+                    (r.w - sign * w).abs()
+                        + (r.x - sign * x).abs()
+                        + (r.y - sign * y).abs()
+                        + (r.z - sign * z).abs()
+                };
+                let (same, opposite) = (difference(1.0), difference(-1.0));
+                assert!(same.min(opposite) < 1e-9, "{:?} != {:?}", r, q);
+                assert_near(Matrix4::from_quaternion(r), m, 1e-12);
+            }
+        }
+    }
+}
@@ -1,6 +1,9 @@
 mod color;
 pub use color::Color;
 
+mod hdr_color;
+pub use hdr_color::{HdrColor, GAMMA};
+
 mod point;
 pub use point::Point;
 
@@ -10,6 +13,11 @@ pub use vector3::Vector3;
 mod vector2;
 pub use vector2::Vector2;
 
-mod matrix;
-pub use crate::mat;
-pub use matrix::Matrix;
+mod vector4;
+pub use vector4::Vector4;
+
+mod matrix4;
+pub use matrix4::Matrix4;
+
+mod quaternion;
+pub use quaternion::Quaternion;
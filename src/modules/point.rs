@@ -47,18 +47,16 @@ impl Point {
     }
 
     /// Linear interpolation
+    #[allow(dead_code)]
     pub fn lerp(
         barycentric: Option<(f64, f64, f64)>,
         triangle: (Self, Self, Self),
     ) -> Option<Self> {
         let (a, b, c) = triangle;
-        match barycentric {
-            Some((alpha, beta, gamma)) => Some(Point {
-                x: ((a.x as f64) * alpha + (b.x as f64) * beta + (c.x as f64) * gamma) as i32,
-                y: ((a.y as f64) * alpha + (b.y as f64) * beta + (c.y as f64) * gamma) as i32,
-            }),
-            None => None,
-        }
+        barycentric.map(|(alpha, beta, gamma)| Point {
+            x: ((a.x as f64) * alpha + (b.x as f64) * beta + (c.x as f64) * gamma) as i32,
+            y: ((a.y as f64) * alpha + (b.y as f64) * beta + (c.y as f64) * gamma) as i32,
+        })
     }
 
     /// Cross product norm with z = 0
@@ -0,0 +1,65 @@
+use std::ops::Mul;
+
+use super::Vector3;
+
+/// Represents a rotation with a unit quaternion w + xi + yj + zk
+#[derive(Copy, Clone, Debug)]
+pub struct Quaternion {
+    pub w: f64,
+    pub x: f64,
+    pub y: f64,
+    pub z: f64,
+}
+
+impl Quaternion {
+    /// Rotation by angle radians around the axis
+    /// (counterclockwise looking from the axis to the origin)
+    #[allow(dead_code)]
+    pub fn from_axis_angle(axis: Vector3, angle: f64) -> Self {
+        let Vector3 { x, y, z } = axis.normalize();
+        let (s, c) = (angle / 2.0).sin_cos();
+        Self {
+            w: c,
+            x: x * s,
+            y: y * s,
+            z: z * s,
+        }
+    }
+
+    /// Norm of the quaternion
+    pub fn norm(self) -> f64 {
+        let Self { w, x, y, z } = self;
+        (w * w + x * x + y * y + z * z).sqrt()
+    }
+
+    /// Quaternion with the same rotation and norm 1
+    pub fn normalize(self) -> Self {
+        let norm = self.norm();
+        Self {
+            w: self.w / norm,
+            x: self.x / norm,
+            y: self.y / norm,
+            z: self.z / norm,
+        }
+    }
+}
+
+// Hamilton product, the rotation other then the rotation self
+impl Mul for Quaternion {
+    type Output = Self;
+    fn mul(self, other: Self) -> Self::Output {
+        let Self { w, x, y, z } = self;
+        let Self {
+            w: ow,
+            x: ox,
+            y: oy,
+            z: oz,
+        } = other;
+        Self {
+            w: w * ow - x * ox - y * oy - z * oz,
+            x: w * ox + x * ow + y * oz - z * oy,
+            y: w * oy - x * oz + y * ow + z * ox,
+            z: w * oz + x * oy - y * ox + z * ow,
+        }
+    }
+}
@@ -1,6 +1,6 @@
 use std::ops::{Add, Div, Mul, Sub};
 
-use super::{Point, Vector3};
+use super::Vector3;
 
 /// Represents a Vector or a Point with 2 Real coordinates
 #[derive(Copy, Clone, Debug)]
@@ -51,21 +51,16 @@ impl Vector2 {
         triangle: (Self, Self, Self),
     ) -> Option<Self> {
         let (a, b, c) = triangle;
-        match barycentric {
-            Some((alpha, beta, gamma)) => Some(a * alpha + b * beta + c * gamma),
-            None => None,
-        }
+        barycentric.map(|(alpha, beta, gamma)| a * alpha + b * beta + c * gamma)
     }
 
     /// Norm of the vector
-    #[allow(dead_code)]
     pub fn norm(self) -> f64 {
         let Self { x, y } = self;
         (x * x + y * y).sqrt()
     }
 
     /// Vector normalized
-    #[allow(dead_code)]
     pub fn normalize(self) -> Self {
         if self.x.abs() < f64::EPSILON && self.y.abs() < f64::EPSILON {
             Self { x: 0.0, y: 0.0 }
@@ -77,24 +72,9 @@ impl Vector2 {
     /// Cross product augmenting the vectors with z = 0
     ///
     /// (x1, y1, 0) ^ (x2, y2, 0) -> (x, y, z) -> (x, y)
-    #[allow(dead_code)]
     pub fn cross(self, other: Vector2) -> f64 {
         self.x * other.y - self.y * other.x
     }
-
-    /// Convert a Vector2 with x, y ∈ [0, 1]
-    /// to a point in the image
-    /// with x ∈ [0, width]
-    /// and  y ∈ [0, height]
-    pub fn to_texture_point(self, width: i32, height: i32) -> Point {
-        // texture_vertex vary from 0 to 1
-        let x = self.x * ((width - 1) as f64);
-        let y = self.y * ((height - 1) as f64);
-        Point {
-            x: x as i32,
-            y: y as i32,
-        }
-    }
 }
 
 impl Add for Vector2 {
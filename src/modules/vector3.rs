@@ -1,6 +1,6 @@
 use std::ops::{Add, Div, Mul, Sub};
 
-use super::{mat, Matrix, Point};
+use super::{Point, Vector4};
 
 /// Represents a Vector or a Point with 3 Real coordinates
 #[derive(Copy, Clone, Debug)]
@@ -12,6 +12,7 @@ pub struct Vector3 {
 
 impl Vector3 {
     /// Caculate barycentric coordinates of a vector v using the triangle
+    #[allow(dead_code)]
     pub fn barycentric(p: Self, triangle: (Self, Self, Self)) -> Option<(f64, f64, f64)> {
         // Barycentric coordinates is the (α, β, 𝛾) where
         // P = α*A + β*B + 𝛾*C and α + β + 𝛾 = 1
@@ -51,10 +52,7 @@ impl Vector3 {
         triangle: (Self, Self, Self),
     ) -> Option<Self> {
         let (a, b, c) = triangle;
-        match barycentric {
-            Some((alpha, beta, gamma)) => Some(a * alpha + b * beta + c * gamma),
-            None => None,
-        }
+        barycentric.map(|(alpha, beta, gamma)| a * alpha + b * beta + c * gamma)
     }
 
     /// Norm of the vector
@@ -102,6 +100,7 @@ impl Vector3 {
     }
 
     /// Convert a Vector3 { x, y, z } to a Point { x, y }
+    #[allow(dead_code)]
     pub fn to_point(self) -> Point {
         Point {
             x: self.x as i32,
@@ -113,6 +112,7 @@ impl Vector3 {
     /// to a point in the image
     /// with x ∈ [0, width]
     /// and  y ∈ [0, height]
+    #[allow(dead_code)]
     pub fn to_image_point(self, width: i32, height: i32) -> Point {
         let x = (self.x + 1.0) * ((width - 1) as f64) / 2.0;
         let y = (self.y + 1.0) * ((height - 1) as f64) / 2.0;
@@ -122,32 +122,22 @@ impl Vector3 {
         }
     }
 
-    /// Convert a point represented by a Vector3
-    /// to a Matrix 4x1
+    /// Convert a point or a vector represented by a Vector3
+    /// to homogeneous coordinates
     ///
     /// If it's represent a point:
-    /// <pre>
-    ///              | x |
-    /// (x, y, z) -> | y |
-    ///              | z |
-    ///              | 1 |
-    /// </pre>
+    /// (x, y, z) -> (x, y, z, 1)
     ///
-    /// <pre>
     /// if it's represent a vector
-    ///              | x |
-    /// (x, y, z) -> | y |
-    ///              | z |
-    ///              | 0 |
-    /// </pre>
-    pub fn to_matrix(self, is_a_point: bool) -> Matrix {
+    /// (x, y, z) -> (x, y, z, 0)
+    pub fn to_vector4(self, is_a_point: bool) -> Vector4 {
         let w = if is_a_point { 1.0 } else { 0.0 };
-        mat![4, 1 =>
-            self.x;
-            self.y;
-            self.z;
-            w;
-        ]
+        Vector4 {
+            x: self.x,
+            y: self.y,
+            z: self.z,
+            w,
+        }
     }
 }
 
@@ -0,0 +1,37 @@
+use super::Vector3;
+
+/// Represents a point or a vector in homogeneous coordinates
+///
+/// Aligned so the 4 coordinates fit in one AVX register
+#[derive(Copy, Clone, Debug)]
+#[repr(C, align(32))]
+pub struct Vector4 {
+    pub x: f64,
+    pub y: f64,
+    pub z: f64,
+    pub w: f64,
+}
+
+impl Vector4 {
+    pub fn to_array(self) -> [f64; 4] {
+        [self.x, self.y, self.z, self.w]
+    }
+
+    /// Convert the homogeneous coordinates to a Vector3
+    ///
+    /// (x, y, z, w) -> (x/w, y/w, z/w)
+    pub fn to_vector3(self) -> Vector3 {
+        let Self { x, y, z, w } = self;
+        if w < f64::EPSILON {
+            // w = 0 => A Vector
+            Vector3 { x, y, z }
+        } else {
+            // w != 0 => A Point
+            Vector3 {
+                x: x / w,
+                y: y / w,
+                z: z / w,
+            }
+        }
+    }
+}
@@ -0,0 +1,304 @@
+use crate::camera::Camera;
+use crate::image::{self, AntiAliasing, Blend, CullMode, Image};
+use crate::light::Light;
+use crate::model::{Model, Vertex};
+use crate::modules::{Matrix4, Vector3};
+use crate::shader::{PhongShader, ShadedFace, Shader};
+use crate::shadow::ShadowMap;
+use crate::transparency::{self, Transparency};
+
+/// A model placed in the scene
+pub struct Instance<'a> {
+    pub model: &'a Model,
+    /// Matrix that convert the coordinates in the model to the scene
+    transform: Matrix4,
+    /// Rows of the cofactor matrix of the linear part of the transform
+    /// used to convert the normals
+    cofactors: [Vector3; 3],
+    /// The transform is a reflection and it changes the winding of the faces
+    mirrored: bool,
+}
+
+impl<'a> Instance<'a> {
+    pub fn new(model: &'a Model, transform: Matrix4) -> Self {
+        // Columns of the linear part
+        let column = |j: usize| Vector3 {
+            x: transform.get(0, j),
+            y: transform.get(1, j),
+            z: transform.get(2, j),
+        };
+        let (a, b, c) = (column(0), column(1), column(2));
+
+        // The normals are converted by the inverse transpose of the linear part,
+        // that is its cofactor matrix divided by the determinant.
+        // Only the direction of the normals is needed then the division is skipped
+        // and the sign of the determinant is kept to not invert them
+        let determinant = a * b.cross(c);
+        let sign = if determinant < 0.0 { -1.0 } else { 1.0 };
+        let cofactors = [b.cross(c) * sign, c.cross(a) * sign, a.cross(b) * sign];
+
+        Self {
+            model,
+            transform,
+            cofactors,
+            mirrored: determinant < 0.0,
+        }
+    }
+
+    /// Convert the vertex of the model to the coordinates of the scene
+    fn vertex(&self, vertex: Vertex) -> Vertex {
+        let direction = |v: Vector3| (self.transform * v.to_vector4(false)).to_vector3();
+        let [i, j, k] = self.cofactors;
+        let n = vertex.normal;
+
+        Vertex {
+            position: (self.transform * vertex.position.to_vector4(true)).to_vector3(),
+            normal: (i * n.x + j * n.y + k * n.z).normalize(),
+            tangent: vertex
+                .tangent
+                .map(|(tangent, bitangent)| (direction(tangent), direction(bitangent))),
+            ..vertex
+        }
+    }
+
+    /// Faces of the model in the coordinates of the scene
+    fn faces(&self) -> impl Iterator<Item = Vec<Vertex>> + '_ {
+        self.model
+            .faces()
+            .map(move |face| face.into_iter().map(|v| self.vertex(v)).collect())
+    }
+
+    /// Matrix that converts the normals of the model to the scene
+    /// (without normalizing them)
+    fn normal_transform(&self) -> Matrix4 {
+        let [i, j, k] = self.cofactors;
+        Matrix4::new([
+            [i.x, j.x, k.x, 0.0],
+            [i.y, j.y, k.y, 0.0],
+            [i.z, j.z, k.z, 0.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ])
+    }
+
+    /// Faces discarded by the rasterizer,
+    /// the front faces of a mirrored model have the reverse winding
+    fn cull_mode(&self) -> CullMode {
+        let cull_mode = self.model.cull_mode();
+        if self.mirrored {
+            CullMode {
+                front: cull_mode.front.reverse(),
+                ..cull_mode
+            }
+        } else {
+            cull_mode
+        }
+    }
+}
+
+/// Many models rendered together in the same image
+/// seen by the same camera and lit by the same lights
+pub struct Scene<'a> {
+    pub camera: Camera,
+    pub lights: Vec<Light>,
+    /// How the faces with transparent materials of all models are drawn
+    pub transparency: Transparency,
+    /// How the edges of the faces are smoothed
+    pub anti_aliasing: AntiAliasing,
+    /// Threads that draw the opaque faces,
+    /// 1 draws them one by one in this thread
+    pub threads: usize,
+    instances: Vec<Instance<'a>>,
+}
+
+impl<'a> Scene<'a> {
+    /// Scene without models and lights
+    pub fn new(camera: Camera) -> Self {
+        Self {
+            camera,
+            lights: Vec::new(),
+            transparency: Transparency::default(),
+            anti_aliasing: AntiAliasing::default(),
+            threads: image::available_threads(),
+            instances: Vec::new(),
+        }
+    }
+
+    /// Add the light to the scene
+    pub fn with_light(mut self, light: Light) -> Self {
+        self.lights.push(light);
+        self
+    }
+
+    /// Add the model to the scene converting its coordinates with the transform
+    ///
+    /// The same model can be added many times with different transforms
+    pub fn with_instance(mut self, model: &'a Model, transform: Matrix4) -> Self {
+        self.instances.push(Instance::new(model, transform));
+        self
+    }
+
+    /// Render all the instances with the same zbuffer,
+    /// each instance uses the shader created by the function for it
+    ///
+    /// The shaders receive the vertices in the coordinates of the scene
+    ///
+    /// The transparent faces of all instances are drawn together
+    /// after the opaque faces
+    /// and the image is smoothed by the anti-aliasing of the scene
+    /// (the anti-aliasing and the threads of the models aren't used)
+    ///
+    /// Return Zbuffer for debug purpose
+    pub fn render<P, S, F>(&self, image: Image<P>, mut shader: F) -> (Image<P>, Vec<f64>)
+    where
+        P: Blend + Send,
+        S: Shader<P> + Clone + Send,
+        F: FnMut(&Instance<'a>) -> S,
+    {
+        image.render_anti_aliased(self.anti_aliasing, |mut image| {
+            let mut zbuffer = image.zbuffer();
+            let mut shaders: Vec<S> = Vec::new();
+            let mut opaque = Vec::new();
+            let mut transparent = Vec::new();
+
+            for (index, instance) in self.instances.iter().enumerate() {
+                shaders.push(shader(instance));
+                let cull_mode = instance.cull_mode();
+                let transparent_materials = instance.model.transparent_materials();
+
+                for face in instance.faces() {
+                    let faces = if transparent_materials[face[0].material] {
+                        &mut transparent
+                    } else {
+                        &mut opaque
+                    };
+                    faces.push(ShadedFace {
+                        shader: index,
+                        vertices: face,
+                        cull_mode,
+                    });
+                }
+            }
+
+            image.faces_tiled(&mut zbuffer, &shaders, &opaque, self.threads);
+
+            transparency::draw_transparent(
+                &mut image,
+                &mut zbuffer,
+                &mut shaders,
+                transparent,
+                self.transparency,
+            );
+
+            (image, zbuffer)
+        })
+    }
+
+    /// Render the scene using the diffuse textures
+    /// and Phong shading with specular highlights
+    ///
+    /// Return Zbuffer for debug purpose
+    #[allow(dead_code)]
+    pub fn render_phong<P>(&self, image: Image<P>) -> (Image<P>, Vec<f64>)
+    where
+        P: Blend + Send,
+        PhongShader<'a>: Shader<P>,
+    {
+        self.render(image, |instance| self.phong_shader(instance))
+    }
+
+    /// Render the scene using the diffuse textures,
+    /// Phong shading with specular highlights
+    /// and the shadows of the first light in the shadow map
+    ///
+    /// Return Zbuffer for debug purpose
+    pub fn render_shadow<P>(
+        &self,
+        image: Image<P>,
+        shadow_map: &'a ShadowMap,
+    ) -> (Image<P>, Vec<f64>)
+    where
+        P: Blend + Send,
+        PhongShader<'a>: Shader<P>,
+    {
+        self.render(image, |instance| {
+            let mut shader = self.phong_shader(instance);
+            shader.shadow_map = Some(shadow_map);
+            shader
+        })
+    }
+
+    /// Phong shader of the instance seen from the camera of the scene
+    fn phong_shader(&self, instance: &Instance<'a>) -> PhongShader<'a> {
+        let mut shader = PhongShader::new(
+            self.camera.transform(),
+            instance.model.materials(),
+            &self.lights,
+            self.camera.eye,
+        );
+        shader.normal_transform = instance.normal_transform();
+        shader
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::material::NormalMap;
+    use crate::modules::{Color, Point};
+    use crate::texture::Texture;
+
+    #[test]
+    fn object_normal_map_rotated_with_instance() {
+        // A triangle facing z with texture coordinates
+        let obj = "v 0 0 0\nv 1 0 0\nv 0 1 0\n\
+                   vt 0 0\nvt 1 0\nvt 0 1\n\
+                   vn 0 0 1\n\
+                   f 1/1/1 2/2/1 3/3/1\n";
+        let path = std::env::temp_dir().join("tinyrenderer_object_normal_map.obj");
+        std::fs::write(&path, obj).unwrap();
+        let model = Model::new(path.to_str().unwrap(), None);
+        std::fs::remove_file(&path).unwrap();
+
+        // All the normals of the map are the x axis of the model
+        let mut normal_map = Image::new(2, 2);
+        for (x, y) in [(0, 0), (1, 0), (0, 1), (1, 1)] {
+            let color = Color {
+                r: 255,
+                g: 128,
+                b: 128,
+                a: 255,
+            };
+            normal_map.set(Point { x, y }, color);
+        }
+        let model = model
+            .unwrap()
+            .with_normal_map(NormalMap::Object(Texture::new(normal_map)));
+
+        let v = |x: f64, y: f64, z: f64| Vector3 { x, y, z };
+        // Rotate the x axis of the model to the y axis of the scene
+        let rotation = Matrix4::rotation(v(0.0, 0.0, 1.0), std::f64::consts::FRAC_PI_2);
+        let camera = Camera::perspective(1.0, 1.0, 0.1, 10.0).look_at(
+            v(0.0, 0.0, 3.0),
+            v(0.0, 0.0, 0.0),
+            v(0.0, 1.0, 0.0),
+        );
+        // Brightness of the center of the triangle lit from the direction
+        let brightness = |direction: Vector3| {
+            let scene = Scene::new(camera)
+                .with_light(Light::directional(direction))
+                .with_instance(&model, rotation);
+            let instance = &scene.instances[0];
+            let face = instance.faces().next().unwrap();
+            let mut shader = scene.phong_shader(instance);
+            for nth in 0..3 {
+                Shader::<Color>::vertex(&mut shader, &face, nth);
+            }
+            let third = 1.0 / 3.0;
+            let Color { r, g, b, .. } = shader.fragment((third, third, third)).unwrap();
+            r as u32 + g as u32 + b as u32
+        };
+        let lit = brightness(v(0.0, 1.0, 0.0));
+        let unlit = brightness(v(1.0, 0.0, 0.0));
+        assert!(lit > unlit + 300, "lit {} unlit {}", lit, unlit);
+    }
+}
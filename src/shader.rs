@@ -0,0 +1,656 @@
+use crate::image::{CullMode, DEPTH};
+use crate::light::{self, Light};
+use crate::material::{Material, NormalMap};
+use crate::model::Vertex;
+use crate::modules::{Color, HdrColor, Matrix4, Vector2, Vector3, Vector4};
+use crate::shadow::ShadowMap;
+use crate::texture::TexturePoint;
+
+/// A programmable shader used by the rasterizer
+///
+/// For each triangle the rasterizer calls the vertex stage
+/// for the vertices 0, 1 and 2 (in this order),
+/// then calls the fragment stage for every pixel inside the triangle
+///
+/// C is the type of the pixels of the image where the shader draws
+pub trait Shader<C = Color> {
+    /// Vertex stage
+    ///
+    /// Receive the face and the index of the vertex in the face,
+    /// return the vertex in clip coordinates
+    /// (the rasterizer clips, divides by w and maps it to the image)
+    /// and save the varyings that will be interpolated in the fragment stage
+    fn vertex(&mut self, face: &[Vertex], nth: usize) -> Vector4;
+
+    /// Fragment stage
+    ///
+    /// Receive the perspective correct barycentric coordinates
+    /// of the pixel in the triangle
+    /// and return the color of the pixel,
+    /// or None if the pixel must be discarded
+    fn fragment(&self, bary: (f64, f64, f64)) -> Option<C>;
+
+    /// Fragment stage that also receives the barycentric coordinates
+    /// of the next pixels in x and y (even if they are outside the triangle),
+    /// used to find the size of the pixel in the textures
+    ///
+    /// The rasterizer calls this stage, by default it calls fragment
+    fn fragment_footprint(
+        &self,
+        bary: (f64, f64, f64),
+        _next: ((f64, f64, f64), (f64, f64, f64)),
+    ) -> Option<C> {
+        self.fragment(bary)
+    }
+}
+
+/// A face waiting to be drawn with the shader in the index
+/// of a list of shaders
+pub struct ShadedFace {
+    pub shader: usize,
+    pub vertices: Vec<Vertex>,
+    pub cull_mode: CullMode,
+}
+
+impl ShadedFace {
+    /// Vertex stage of the three vertices of the face
+    pub fn vertex_stage<P, S: Shader<P>>(&self, shaders: &mut [S]) -> (Vector4, Vector4, Vector4) {
+        let shader = &mut shaders[self.shader];
+        (
+            shader.vertex(&self.vertices, 0),
+            shader.vertex(&self.vertices, 1),
+            shader.vertex(&self.vertices, 2),
+        )
+    }
+}
+
+/// Transform a vertex in the model coordinates using the transform matrix
+/// to the clip coordinates
+fn transform_vertex(transform: &Matrix4, vertex: Vector3) -> Vector4 {
+    *transform * vertex.to_vector4(true)
+}
+
+/// Interpolate the texture vertex of the pixel
+///
+/// Return None if a vertex has no texture vertex
+fn interpolate_texture(textures: [Option<Vector2>; 3], bary: (f64, f64, f64)) -> Option<Vector2> {
+    match textures {
+        [Some(ut), Some(vt), Some(wt)] => Vector2::lerp(Some(bary), (ut, vt, wt)),
+        _ => None,
+    }
+}
+
+/// Interpolate the texture vertex of the pixel
+/// and the texture vertices of the next pixels in x and y
+///
+/// Return None if a vertex has no texture vertex
+fn interpolate_texture_point(
+    textures: [Option<Vector2>; 3],
+    bary: (f64, f64, f64),
+    (next_x, next_y): ((f64, f64, f64), (f64, f64, f64)),
+) -> Option<TexturePoint> {
+    let t = interpolate_texture(textures, bary)?;
+    Some(TexturePoint {
+        t,
+        dx: interpolate_texture(textures, next_x)? - t,
+        dy: interpolate_texture(textures, next_y)? - t,
+    })
+}
+
+/// Intensity of each light in the face
+/// using the center and the normal of the face
+fn face_intensities(face: &[Vertex], lights: &[Light]) -> Vec<f64> {
+    let u = face[0].position;
+    let v = face[1].position;
+    let w = face[2].position;
+    light::intensities(lights, (u + v + w) / 3.0, Vector3::normal(u, v, w))
+}
+
+/// Interpolate the intensity of each light in the pixel
+fn interpolate_intensities(
+    [u, v, w]: &[Vec<f64>; 3],
+    (alpha, beta, gamma): (f64, f64, f64),
+) -> impl Iterator<Item = f64> + '_ {
+    u.iter()
+        .zip(v)
+        .zip(w)
+        .map(move |((u, v), w)| u * alpha + v * beta + w * gamma)
+}
+
+/// Interpolate the normal of the pixel
+/// and perturb it with the normal map of the material in the texture point t
+///
+/// The normals of a normal map in the model coordinates
+/// are converted to the coordinates of the vertices by the normal transform
+fn interpolate_normal(
+    material: &Material,
+    bary: (f64, f64, f64),
+    t: Option<TexturePoint>,
+    [un, vn, wn]: [Vector3; 3],
+    tangents: [Option<(Vector3, Vector3)>; 3],
+    normal_transform: &Matrix4,
+) -> Vector3 {
+    let n = Vector3::lerp(Some(bary), (un, vn, wn)).unwrap().normalize();
+    let (normal_map, t) = match (&material.normal_map, t) {
+        (Some(normal_map), Some(t)) => (normal_map, t),
+        _ => return n,
+    };
+
+    let tangent = match tangents {
+        [Some((ut, ub)), Some((vt, vb)), Some((wt, wb))] => Some((
+            Vector3::lerp(Some(bary), (ut, vt, wt)).unwrap(),
+            Vector3::lerp(Some(bary), (ub, vb, wb)).unwrap(),
+        )),
+        _ => None,
+    };
+
+    let normal = normal_map.normal(t, material.sampler, n, tangent);
+    match normal_map {
+        NormalMap::Object(_) => (*normal_transform * normal.to_vector4(false))
+            .to_vector3()
+            .normalize(),
+        NormalMap::Tangent(_) => normal,
+    }
+}
+
+/// Fill the triangles with the depth in gray
+///
+/// It's used to render only the zbuffer
+#[derive(Clone)]
+pub struct DepthShader {
+    pub transform: Matrix4,
+
+    // Varying
+    /// (z, w) in clip coordinates
+    depths: [(f64, f64); 3],
+}
+
+impl DepthShader {
+    pub fn new(transform: Matrix4) -> Self {
+        Self {
+            transform,
+            depths: [(0.0, 1.0); 3],
+        }
+    }
+}
+
+impl Shader for DepthShader {
+    fn vertex(&mut self, face: &[Vertex], nth: usize) -> Vector4 {
+        let v = transform_vertex(&self.transform, face[nth].position);
+        self.depths[nth] = (v.z, v.w);
+        v
+    }
+
+    fn fragment(&self, (alpha, beta, gamma): (f64, f64, f64)) -> Option<Color> {
+        let [(uz, uw), (vz, vw), (wz, ww)] = self.depths;
+        // z and w are linear in clip coordinates, z / w isn't
+        let z = uz * alpha + vz * beta + wz * gamma;
+        let w = uw * alpha + vw * beta + ww * gamma;
+        Some(Color::gray(((z / w + 1.0) * DEPTH / 2.0) as u8))
+    }
+}
+
+/// Fill the triangles with a color using flat shading
+#[derive(Clone)]
+pub struct FlatShader {
+    pub transform: Matrix4,
+    pub color: Color,
+    pub lights: Vec<Light>,
+
+    // Varying
+    face_color: Color,
+}
+
+impl FlatShader {
+    pub fn new(transform: Matrix4, color: Color, lights: &[Light]) -> Self {
+        Self {
+            transform,
+            color,
+            lights: lights.to_vec(),
+            face_color: color,
+        }
+    }
+}
+
+impl Shader for FlatShader {
+    fn vertex(&mut self, face: &[Vertex], nth: usize) -> Vector4 {
+        if nth == 0 {
+            let intensities = face_intensities(face, &self.lights);
+            self.face_color = light::shade(self.color, &self.lights, intensities.into_iter());
+        }
+        let v = face[nth].position;
+        transform_vertex(&self.transform, v)
+    }
+
+    fn fragment(&self, _bary: (f64, f64, f64)) -> Option<Color> {
+        Some(self.face_color)
+    }
+}
+
+/// Fill the triangles with the diffuse texture of the material of each face
+/// using flat shading
+#[derive(Clone)]
+pub struct TextureShader<'a> {
+    pub transform: Matrix4,
+    pub materials: &'a [Material],
+    pub lights: Vec<Light>,
+
+    // Varyings
+    material: usize,
+    intensities: Vec<f64>,
+    textures: [Option<Vector2>; 3],
+}
+
+impl<'a> TextureShader<'a> {
+    pub fn new(transform: Matrix4, materials: &'a [Material], lights: &[Light]) -> Self {
+        Self {
+            transform,
+            materials,
+            lights: lights.to_vec(),
+            material: 0,
+            intensities: Vec::new(),
+            textures: [None; 3],
+        }
+    }
+}
+
+impl<'a> Shader for TextureShader<'a> {
+    fn vertex(&mut self, face: &[Vertex], nth: usize) -> Vector4 {
+        if nth == 0 {
+            self.intensities = face_intensities(face, &self.lights);
+            self.material = face[nth].material;
+        }
+        let v = face[nth].position;
+        self.textures[nth] = face[nth].texture;
+        transform_vertex(&self.transform, v)
+    }
+
+    fn fragment(&self, bary: (f64, f64, f64)) -> Option<Color> {
+        self.fragment_footprint(bary, (bary, bary))
+    }
+
+    fn fragment_footprint(
+        &self,
+        bary: (f64, f64, f64),
+        next: ((f64, f64, f64), (f64, f64, f64)),
+    ) -> Option<Color> {
+        let t = interpolate_texture_point(self.textures, bary, next);
+        let color = self.materials[self.material].diffuse_color(t);
+        let intensities = self.intensities.iter().copied();
+        Some(light::shade(color, &self.lights, intensities))
+    }
+}
+
+/// Fill the triangles with a color using Gouraud shading
+#[derive(Clone)]
+pub struct GouraudShader {
+    pub transform: Matrix4,
+    pub color: Color,
+    pub lights: Vec<Light>,
+
+    // Varying
+    /// Intensity of each light in the vertices
+    intensities: [Vec<f64>; 3],
+}
+
+impl GouraudShader {
+    pub fn new(transform: Matrix4, color: Color, lights: &[Light]) -> Self {
+        Self {
+            transform,
+            color,
+            lights: lights.to_vec(),
+            intensities: Default::default(),
+        }
+    }
+}
+
+impl Shader for GouraudShader {
+    fn vertex(&mut self, face: &[Vertex], nth: usize) -> Vector4 {
+        let Vertex {
+            position, normal, ..
+        } = face[nth];
+        self.intensities[nth] = light::intensities(&self.lights, position, normal);
+        transform_vertex(&self.transform, position)
+    }
+
+    fn fragment(&self, bary: (f64, f64, f64)) -> Option<Color> {
+        let intensities = interpolate_intensities(&self.intensities, bary);
+        Some(light::shade(self.color, &self.lights, intensities))
+    }
+}
+
+/// Fill the triangles with the diffuse texture of the material of each face
+/// using Gouraud shading
+///
+/// The light is calculated in the model coordinates,
+/// if the material has a normal map it's calculated in each pixel
+/// using the normal in the normal map
+#[derive(Clone)]
+pub struct GouraudTextureShader<'a> {
+    pub transform: Matrix4,
+    pub materials: &'a [Material],
+    pub lights: Vec<Light>,
+
+    // Varyings
+    material: usize,
+    /// Intensity of each light in the vertices
+    intensities: [Vec<f64>; 3],
+    positions: [Vector3; 3],
+    textures: [Option<Vector2>; 3],
+    normals: [Vector3; 3],
+    tangents: [Option<(Vector3, Vector3)>; 3],
+}
+
+impl<'a> GouraudTextureShader<'a> {
+    pub fn new(transform: Matrix4, materials: &'a [Material], lights: &[Light]) -> Self {
+        let zero = Vector3 {
+            x: 0.0,
+            y: 0.0,
+            z: 0.0,
+        };
+        Self {
+            transform,
+            materials,
+            lights: lights.to_vec(),
+            material: 0,
+            intensities: Default::default(),
+            positions: [zero; 3],
+            textures: [None; 3],
+            normals: [zero; 3],
+            tangents: [None; 3],
+        }
+    }
+}
+
+impl<'a> Shader for GouraudTextureShader<'a> {
+    fn vertex(&mut self, face: &[Vertex], nth: usize) -> Vector4 {
+        let Vertex {
+            position,
+            texture,
+            normal,
+            tangent,
+            material,
+        } = face[nth];
+        self.material = material;
+        self.intensities[nth] = light::intensities(&self.lights, position, normal);
+        self.positions[nth] = position;
+        self.textures[nth] = texture;
+        self.normals[nth] = normal;
+        self.tangents[nth] = tangent;
+        transform_vertex(&self.transform, position)
+    }
+
+    fn fragment(&self, bary: (f64, f64, f64)) -> Option<Color> {
+        self.fragment_footprint(bary, (bary, bary))
+    }
+
+    fn fragment_footprint(
+        &self,
+        bary: (f64, f64, f64),
+        next: ((f64, f64, f64), (f64, f64, f64)),
+    ) -> Option<Color> {
+        let material = &self.materials[self.material];
+        let t = interpolate_texture_point(self.textures, bary, next);
+
+        let color = material.diffuse_color(t);
+
+        match material.normal_map {
+            Some(_) => {
+                let n = interpolate_normal(
+                    material,
+                    bary,
+                    t,
+                    self.normals,
+                    self.tangents,
+                    &Matrix4::identity(),
+                );
+                let [u, v, w] = self.positions;
+                let position = Vector3::lerp(Some(bary), (u, v, w)).unwrap();
+                let intensities = light::intensities(&self.lights, position, n);
+                Some(light::shade(color, &self.lights, intensities.into_iter()))
+            }
+            None => {
+                let intensities = interpolate_intensities(&self.intensities, bary);
+                Some(light::shade(color, &self.lights, intensities))
+            }
+        }
+    }
+}
+
+/// Fill the triangles with the diffuse texture of the material of each face
+/// using Phong shading and the Blinn–Phong reflection model
+///
+/// The light is calculated in each pixel in the model coordinates
+/// and it's the sum of an ambient light and the diffuse and specular lights
+/// of each light with the colors of the material
+///
+/// The lights are summed with floating point colors,
+/// in an HdrImage the 8-bit colors are converted from the gamma to linear
+/// and in an Image the sum is clamped
+///
+/// If there is a shadow map, the diffuse and specular lights
+/// of the first light are multiplied by the visibility of the pixel
+#[derive(Clone)]
+pub struct PhongShader<'a> {
+    pub transform: Matrix4,
+    pub materials: &'a [Material],
+    pub shadow_map: Option<&'a ShadowMap>,
+    pub lights: Vec<Light>,
+    /// Point where the camera is
+    pub eye: Vector3,
+    /// Matrix that converts the normals of the normal maps
+    /// in the model coordinates to the coordinates of the vertices
+    pub normal_transform: Matrix4,
+
+    // Varyings
+    material: usize,
+    positions: [Vector3; 3],
+    textures: [Option<Vector2>; 3],
+    normals: [Vector3; 3],
+    tangents: [Option<(Vector3, Vector3)>; 3],
+}
+
+impl<'a> PhongShader<'a> {
+    pub fn new(
+        transform: Matrix4,
+        materials: &'a [Material],
+        lights: &[Light],
+        eye: Vector3,
+    ) -> Self {
+        let zero = Vector3 {
+            x: 0.0,
+            y: 0.0,
+            z: 0.0,
+        };
+        Self {
+            transform,
+            materials,
+            shadow_map: None,
+            lights: lights.to_vec(),
+            eye,
+            normal_transform: Matrix4::identity(),
+            material: 0,
+            positions: [zero; 3],
+            textures: [None; 3],
+            normals: [zero; 3],
+            tangents: [None; 3],
+        }
+    }
+}
+
+impl<'a> PhongShader<'a> {
+    /// Vertex stage shared by the 8-bit and the HDR images
+    fn vertex_stage(&mut self, face: &[Vertex], nth: usize) -> Vector4 {
+        let Vertex {
+            position,
+            texture,
+            normal,
+            tangent,
+            material,
+        } = face[nth];
+        self.material = material;
+        self.positions[nth] = position;
+        self.textures[nth] = texture;
+        self.normals[nth] = normal;
+        self.tangents[nth] = tangent;
+        transform_vertex(&self.transform, position)
+    }
+
+    /// Light in the pixel and the alpha of the diffuse color,
+    /// the 8-bit colors are converted with decode
+    ///
+    /// next are the barycentric coordinates of the next pixels in x and y
+    fn radiance(
+        &self,
+        bary: (f64, f64, f64),
+        next: ((f64, f64, f64), (f64, f64, f64)),
+        decode: impl Fn(Color) -> HdrColor,
+    ) -> (HdrColor, u8) {
+        let material = &self.materials[self.material];
+        let [u, v, w] = self.positions;
+        let t = interpolate_texture_point(self.textures, bary, next);
+        let position = Vector3::lerp(Some(bary), (u, v, w)).unwrap();
+        let n = interpolate_normal(
+            material,
+            bary,
+            t,
+            self.normals,
+            self.tangents,
+            &self.normal_transform,
+        );
+
+        let view = (self.eye - position).normalize();
+        let diffuse_color = material.diffuse_color(t);
+        let color = decode(diffuse_color);
+        let specular_color = decode(material.specular);
+
+        let mut result = color * decode(material.ambient);
+        for (i, light) in self.lights.iter().enumerate() {
+            let (l, intensity) = light.incidence(position);
+            // Halfway vector between the light and the view
+            let halfway = (l + view).normalize();
+
+            let diffuse = (n * l).max(0.0);
+            let specular = if diffuse > 0.0 {
+                (n * halfway).max(0.0).powf(material.shininess(t))
+            } else {
+                0.0
+            };
+
+            let visibility = match self.shadow_map {
+                Some(shadow_map) if i == 0 => shadow_map.visibility(position),
+                _ => 1.0,
+            };
+
+            let intensity = (intensity * visibility) as f32;
+            let light_color = decode(light.color()) * intensity;
+            result = result
+                + color * light_color * (diffuse as f32)
+                + specular_color * light_color * (specular as f32);
+        }
+
+        (result, diffuse_color.a)
+    }
+}
+
+impl<'a> Shader for PhongShader<'a> {
+    fn vertex(&mut self, face: &[Vertex], nth: usize) -> Vector4 {
+        self.vertex_stage(face, nth)
+    }
+
+    fn fragment(&self, bary: (f64, f64, f64)) -> Option<Color> {
+        let (radiance, alpha) = self.radiance(bary, (bary, bary), HdrColor::from);
+        Some(radiance.to_color().with_alpha(alpha))
+    }
+
+    fn fragment_footprint(
+        &self,
+        bary: (f64, f64, f64),
+        next: ((f64, f64, f64), (f64, f64, f64)),
+    ) -> Option<Color> {
+        let (radiance, alpha) = self.radiance(bary, next, HdrColor::from);
+        Some(radiance.to_color().with_alpha(alpha))
+    }
+}
+
+impl<'a> Shader<HdrColor> for PhongShader<'a> {
+    fn vertex(&mut self, face: &[Vertex], nth: usize) -> Vector4 {
+        self.vertex_stage(face, nth)
+    }
+
+    fn fragment(&self, bary: (f64, f64, f64)) -> Option<HdrColor> {
+        let (radiance, _) = self.radiance(bary, (bary, bary), HdrColor::from_srgb);
+        Some(radiance)
+    }
+
+    fn fragment_footprint(
+        &self,
+        bary: (f64, f64, f64),
+        next: ((f64, f64, f64), (f64, f64, f64)),
+    ) -> Option<HdrColor> {
+        let (radiance, _) = self.radiance(bary, next, HdrColor::from_srgb);
+        Some(radiance)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::camera::Camera;
+    use crate::image::Image;
+    use crate::modules::Point;
+    use crate::texture::Texture;
+
+    #[test]
+    fn gouraud_texture_independent_of_eye() {
+        let v = |x: f64, y: f64, z: f64| Vector3 { x, y, z };
+        let vertex = |position: Vector3, texture: Vector2| Vertex {
+            position,
+            texture: Some(texture),
+            normal: (position - v(0.0, 0.0, -1.0)).normalize(),
+            tangent: Some((v(1.0, 0.0, 0.0), v(0.0, 1.0, 0.0))),
+            material: 0,
+        };
+        let face = [
+            vertex(v(-0.5, -0.5, 0.0), Vector2 { x: 0.0, y: 0.0 }),
+            vertex(v(0.5, -0.5, 0.1), Vector2 { x: 1.0, y: 0.0 }),
+            vertex(v(0.0, 0.5, -0.1), Vector2 { x: 0.5, y: 1.0 }),
+        ];
+        let lights = [Light::Point {
+            position: v(1.0, 2.0, 1.5),
+            color: Color::gray(255),
+            intensity: 2.0,
+            attenuation: (1.0, 0.1, 0.0),
+        }];
+
+        // A tangent normal map tilted to the tangent
+        let mut normal_map = Image::new(2, 2);
+        for (x, y) in [(0, 0), (1, 0), (0, 1), (1, 1)] {
+            let color = Color {
+                r: 200,
+                g: 128,
+                b: 220,
+                a: 255,
+            };
+            normal_map.set(Point { x, y }, color);
+        }
+        let mut mapped = Material::new("mapped");
+        mapped.normal_map = Some(NormalMap::Tangent(Texture::new(normal_map)));
+
+        for materials in [[Material::new("plain")], [mapped]] {
+            let color = |eye: Vector3| {
+                let camera = Camera::perspective(1.0, 1.0, 0.1, 10.0).look_at(
+                    eye,
+                    v(0.0, 0.0, 0.0),
+                    v(0.0, 1.0, 0.0),
+                );
+                let mut shader = GouraudTextureShader::new(camera.transform(), &materials, &lights);
+                for nth in 0..3 {
+                    shader.vertex(&face, nth);
+                }
+                shader.fragment((0.2, 0.3, 0.5)).unwrap()
+            };
+            assert_eq!(color(v(0.0, 0.0, 3.0)), color(v(2.5, 1.0, 1.5)));
+        }
+    }
+}
@@ -0,0 +1,92 @@
+use crate::camera::Camera;
+use crate::image::Image;
+use crate::model::Model;
+use crate::modules::{Matrix4, Vector3};
+use crate::scene::Scene;
+use crate::shader::DepthShader;
+
+/// Depth of a model seen from the light
+/// used to test if a point is in the shadow
+pub struct ShadowMap {
+    width: i32,
+    height: i32,
+    /// Zbuffer of the model rendered from the light's point of view
+    depth: Vec<f64>,
+    /// Matrix that convert a point in the model to the shadow map
+    transform: Matrix4,
+
+    /// Value added to the depth of the point before the test
+    /// to avoid a surface shadowing itself (shadow acne)
+    pub bias: f64,
+    /// Radius of the percentage closer filtering,
+    /// the visibility is the average of (2 * pcf + 1)² tests
+    pub pcf: i32,
+}
+
+impl ShadowMap {
+    /// Render the depth of the model seen from the light
+    /// with a camera in the light looking in the direction of the light
+    /// (usually an orthographic camera for a directional light)
+    pub fn new(model: &Model, width: i32, height: i32, camera: &Camera) -> Self {
+        let mut shader = DepthShader::new(camera.transform());
+        let (image, depth) = model.render(Image::new(width, height), &mut shader);
+        Self::from_depth(&image, depth, camera)
+    }
+
+    /// Render the depth of all the models in the scene seen from the light
+    ///
+    /// The points tested are in the coordinates of the scene
+    pub fn from_scene(scene: &Scene, width: i32, height: i32, camera: &Camera) -> Self {
+        let (image, depth) = scene.render(Image::new(width, height), |_| {
+            DepthShader::new(camera.transform())
+        });
+        Self::from_depth(&image, depth, camera)
+    }
+
+    /// Shadow map with the zbuffer of the image rendered by the camera
+    fn from_depth(image: &Image, depth: Vec<f64>, camera: &Camera) -> Self {
+        // Transformation chain: Viewport * Projection * View * Model * v
+        let transform = image.viewport() * camera.transform();
+
+        Self {
+            width: image.width,
+            height: image.height,
+            depth,
+            transform,
+            bias: 3.0,
+            pcf: 1,
+        }
+    }
+
+    /// Fraction of the light that reaches the point in the model coordinates
+    ///
+    /// 0 means that the point is in the shadow and 1 that it's lit
+    pub fn visibility(&self, point: Vector3) -> f64 {
+        let p = (self.transform * point.to_vector4(true)).to_vector3();
+        let (x, y) = (p.x as i32, p.y as i32);
+
+        let mut lit = 0;
+        let mut count = 0;
+        for dy in -self.pcf..=self.pcf {
+            for dx in -self.pcf..=self.pcf {
+                count += 1;
+                if p.z + self.bias >= self.depth(x + dx, y + dy) {
+                    lit += 1;
+                }
+            }
+        }
+
+        (lit as f64) / (count as f64)
+    }
+
+    /// Depth in the shadow map (x, y)
+    ///
+    /// Outside the shadow map nothing is hiding the light
+    fn depth(&self, x: i32, y: i32) -> f64 {
+        if 0 <= x && x < self.width && 0 <= y && y < self.height {
+            self.depth[(y * self.width + x) as usize]
+        } else {
+            f64::NEG_INFINITY
+        }
+    }
+}
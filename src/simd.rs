@@ -0,0 +1,275 @@
+/// Pixels of a row tested together by the rasterizer
+pub const LANES: usize = 4;
+
+/// A value for each pixel of the lanes
+pub type Lanes = [f64; LANES];
+
+/// Test if the processor has AVX (the result is cached)
+#[cfg(target_arch = "x86_64")]
+pub fn has_avx() -> bool {
+    is_x86_feature_detected!("avx")
+}
+
+/// Triangle tested and interpolated in the lanes
+///
+/// The edge functions are integers in fixed point
+/// kept in f64 without loss (they are smaller than 2^53)
+/// and every implementation does the same operations in the same order,
+/// so the results don't depend on the instructions of the processor
+pub struct Triangle {
+    /// The sample is inside if the edge function is greater than the threshold
+    pub thresholds: [f64; 3],
+    /// Change of each edge function from the first lane to each lane
+    pub steps: [Lanes; 3],
+    /// z of each vertex
+    pub z: [f64; 3],
+    /// 1 / the sum of the edge functions (twice the area of the triangle)
+    pub inverse_area: f64,
+    /// 1 / w of each vertex, to interpolate in perspective
+    pub inverse_w: [f64; 3],
+    /// Barycentric coordinates of each vertex in the original triangle
+    /// (before the triangle was clipped)
+    pub original: [[f64; 3]; 3],
+}
+
+/// Fragments of a sample in the lanes
+pub struct Fragments {
+    /// Bit l is set if the sample of the lane l is inside the triangle
+    /// and it passes the depth test
+    pub mask: u32,
+    /// Perspective correct barycentric coordinates in the original triangle
+    pub bary: [Lanes; 3],
+    pub z: Lanes,
+}
+
+impl Triangle {
+    /// Perspective correct barycentric coordinates in the original triangle
+    /// of the point with the values of the edge functions
+    ///
+    /// The barycentric coordinates in the screen are divided
+    /// by the w of each vertex and normalized,
+    /// so the attributes don't deform with the perspective
+    pub fn barycentric(&self, edges: [f64; 3]) -> (f64, f64, f64) {
+        let screen = edges.map(|e| e * self.inverse_area);
+        let bary: [f64; 3] = std::array::from_fn(|j| screen[j] * self.inverse_w[j]);
+        let inverse_sum = 1.0 / (bary[0] + bary[1] + bary[2]);
+        let bary = bary.map(|b| b * inverse_sum);
+        let o = self.original;
+        let original = |k: usize| bary[0] * o[0][k] + bary[1] * o[1][k] + bary[2] * o[2][k];
+        (original(0), original(1), original(2))
+    }
+
+    /// Test the sample in each lane with the values of the edge functions
+    /// in the first lane and the depths in the zbuffer,
+    /// the barycentric coordinates are only valid in the lanes of the mask
+    ///
+    /// Uses SSE2, that every x86_64 processor has
+    #[cfg(target_arch = "x86_64")]
+    #[inline]
+    pub fn fragments(&self, edges: [f64; 3], depths: &Lanes) -> Fragments {
+        // Safe because SSE2 is enabled in every x86_64 build
+        unsafe { self.fragments_sse2(edges, depths) }
+    }
+
+    /// Test the sample in each lane with the values of the edge functions
+    /// in the first lane and the depths in the zbuffer,
+    /// the barycentric coordinates are only valid in the lanes of the mask
+    #[cfg(not(target_arch = "x86_64"))]
+    #[inline]
+    pub fn fragments(&self, edges: [f64; 3], depths: &Lanes) -> Fragments {
+        self.fragments_scalar(edges, depths)
+    }
+
+    /// The lanes one by one, used where there is no SIMD implementation
+    #[cfg_attr(target_arch = "x86_64", allow(dead_code))]
+    #[inline]
+    fn fragments_scalar(&self, edges: [f64; 3], depths: &Lanes) -> Fragments {
+        let mut fragments = Fragments {
+            mask: 0,
+            bary: [[0.0; LANES]; 3],
+            z: [0.0; LANES],
+        };
+        for (l, &depth) in depths.iter().enumerate() {
+            let e: [f64; 3] = std::array::from_fn(|j| edges[j] + self.steps[j][l]);
+            if !(0..3).all(|j| e[j] > self.thresholds[j]) {
+                continue;
+            }
+            let screen = e.map(|e| e * self.inverse_area);
+            let z = screen[0] * self.z[0] + screen[1] * self.z[1] + screen[2] * self.z[2];
+            if z > depth {
+                fragments.mask |= 1 << l;
+                let bary = self.barycentric(e);
+                fragments.bary[0][l] = bary.0;
+                fragments.bary[1][l] = bary.1;
+                fragments.bary[2][l] = bary.2;
+            }
+            fragments.z[l] = z;
+        }
+        fragments
+    }
+
+    /// The lanes in 2 registers of 2 values
+    #[cfg(target_arch = "x86_64")]
+    #[inline]
+    unsafe fn fragments_sse2(&self, edges: [f64; 3], depths: &Lanes) -> Fragments {
+        use std::arch::x86_64::*;
+
+        let mut fragments = Fragments {
+            mask: 0,
+            bary: [[0.0; LANES]; 3],
+            z: [0.0; LANES],
+        };
+        let splat = |v: f64| _mm_set1_pd(v);
+        let dot = |a: [__m128d; 3], b: [f64; 3]| {
+            let sum = _mm_add_pd(_mm_mul_pd(a[0], splat(b[0])), _mm_mul_pd(a[1], splat(b[1])));
+            _mm_add_pd(sum, _mm_mul_pd(a[2], splat(b[2])))
+        };
+
+        for half in [0, 2] {
+            let e = [0, 1, 2].map(|j| {
+                _mm_add_pd(
+                    splat(edges[j]),
+                    _mm_loadu_pd(self.steps[j].as_ptr().add(half)),
+                )
+            });
+            let mut inside = _mm_castsi128_pd(_mm_set1_epi64x(-1));
+            for (e, threshold) in e.iter().zip(self.thresholds) {
+                inside = _mm_and_pd(inside, _mm_cmpgt_pd(*e, splat(threshold)));
+            }
+            // Most of the lanes in the box of a small triangle are outside
+            if _mm_movemask_pd(inside) == 0 {
+                continue;
+            }
+
+            let screen = e.map(|e| _mm_mul_pd(e, splat(self.inverse_area)));
+            let z = dot(screen, self.z);
+            let closer = _mm_cmpgt_pd(z, _mm_loadu_pd(depths.as_ptr().add(half)));
+            let mask = _mm_movemask_pd(_mm_and_pd(inside, closer)) as u32;
+            fragments.mask |= mask << half;
+            _mm_storeu_pd(fragments.z.as_mut_ptr().add(half), z);
+            if mask == 0 {
+                continue;
+            }
+
+            let bary = [0, 1, 2].map(|j| _mm_mul_pd(screen[j], splat(self.inverse_w[j])));
+            let sum = _mm_add_pd(_mm_add_pd(bary[0], bary[1]), bary[2]);
+            let inverse_sum = _mm_div_pd(splat(1.0), sum);
+            let bary = bary.map(|b| _mm_mul_pd(b, inverse_sum));
+            for (k, lanes) in fragments.bary.iter_mut().enumerate() {
+                let original = dot(bary, self.original.map(|o| o[k]));
+                _mm_storeu_pd(lanes.as_mut_ptr().add(half), original);
+            }
+        }
+        fragments
+    }
+
+    /// Like fragments with the lanes in 1 register of 4 values
+    ///
+    /// The processor must have AVX
+    #[cfg(target_arch = "x86_64")]
+    #[target_feature(enable = "avx")]
+    #[inline]
+    pub unsafe fn fragments_avx(&self, edges: [f64; 3], depths: &Lanes) -> Fragments {
+        use std::arch::x86_64::*;
+
+        let mut fragments = Fragments {
+            mask: 0,
+            bary: [[0.0; LANES]; 3],
+            z: [0.0; LANES],
+        };
+        let splat = |v: f64| _mm256_set1_pd(v);
+        let dot = |a: [__m256d; 3], b: [f64; 3]| {
+            let sum = _mm256_add_pd(
+                _mm256_mul_pd(a[0], splat(b[0])),
+                _mm256_mul_pd(a[1], splat(b[1])),
+            );
+            _mm256_add_pd(sum, _mm256_mul_pd(a[2], splat(b[2])))
+        };
+
+        let e = [0, 1, 2]
+            .map(|j| _mm256_add_pd(splat(edges[j]), _mm256_loadu_pd(self.steps[j].as_ptr())));
+        let mut inside = _mm256_castsi256_pd(_mm256_set1_epi64x(-1));
+        for (e, threshold) in e.iter().zip(self.thresholds) {
+            inside = _mm256_and_pd(inside, _mm256_cmp_pd::<_CMP_GT_OQ>(*e, splat(threshold)));
+        }
+        // Most of the lanes in the box of a small triangle are outside
+        if _mm256_movemask_pd(inside) == 0 {
+            return fragments;
+        }
+
+        let screen = e.map(|e| _mm256_mul_pd(e, splat(self.inverse_area)));
+        let z = dot(screen, self.z);
+        let closer = _mm256_cmp_pd::<_CMP_GT_OQ>(z, _mm256_loadu_pd(depths.as_ptr()));
+        fragments.mask = _mm256_movemask_pd(_mm256_and_pd(inside, closer)) as u32;
+        _mm256_storeu_pd(fragments.z.as_mut_ptr(), z);
+        if fragments.mask == 0 {
+            return fragments;
+        }
+
+        let bary = [0, 1, 2].map(|j| _mm256_mul_pd(screen[j], splat(self.inverse_w[j])));
+        let sum = _mm256_add_pd(_mm256_add_pd(bary[0], bary[1]), bary[2]);
+        let inverse_sum = _mm256_div_pd(splat(1.0), sum);
+        let bary = bary.map(|b| _mm256_mul_pd(b, inverse_sum));
+        for (k, lanes) in fragments.bary.iter_mut().enumerate() {
+            let original = dot(bary, self.original.map(|o| o[k]));
+            _mm256_storeu_pd(lanes.as_mut_ptr(), original);
+        }
+        fragments
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Test that the implementations have the same mask
+    /// and the same depths and barycentric coordinates in the lanes of the mask
+    fn assert_same(a: &Fragments, b: &Fragments) {
+        assert_eq!(a.mask, b.mask);
+        for l in (0..LANES).filter(|l| a.mask & (1 << l) != 0) {
+            assert_eq!(a.z[l].to_bits(), b.z[l].to_bits());
+            for k in 0..3 {
+                assert_eq!(a.bary[k][l].to_bits(), b.bary[k][l].to_bits());
+            }
+        }
+    }
+
+    #[test]
+    fn implementations_are_identical() {
+        // A linear congruential generator
+        let mut seed: u64 = 1;
+        let mut random = |max: f64| {
+            seed = seed.wrapping_mul(6364136223846793005).wrapping_add(1);
+            (seed >> 11) as f64 / (1u64 << 53) as f64 * max
+        };
+
+        let mut masks = 0;
+        for _ in 0..10000 {
+            let step: [f64; 3] = std::array::from_fn(|_| (random(512.0) - 256.0).round());
+            let triangle = Triangle {
+                thresholds: std::array::from_fn(|_| -(random(2.0).floor())),
+                steps: step.map(|step| std::array::from_fn(|l| l as f64 * step)),
+                z: std::array::from_fn(|_| random(2.0) - 1.0),
+                inverse_area: 1.0 / (random(1e6) + 1.0),
+                inverse_w: std::array::from_fn(|_| 1.0 / (random(10.0) + 0.1)),
+                original: std::array::from_fn(|_| std::array::from_fn(|_| random(1.0))),
+            };
+            // Edge functions around 0 to have lanes inside and outside
+            let edges = std::array::from_fn(|_| (random(2048.0) - 1024.0).round());
+            let depths = std::array::from_fn(|_| random(2.0) - 1.0);
+
+            let scalar = triangle.fragments_scalar(edges, &depths);
+            masks += (scalar.mask != 0) as u32;
+            #[cfg(target_arch = "x86_64")]
+            {
+                assert_same(&scalar, &unsafe { triangle.fragments_sse2(edges, &depths) });
+                if has_avx() {
+                    assert_same(&scalar, &unsafe { triangle.fragments_avx(edges, &depths) });
+                }
+            }
+            assert_same(&scalar, &triangle.fragments(edges, &depths));
+        }
+        // Many lanes were drawn
+        assert!(masks > 1000, "Only {} masks", masks);
+    }
+}
@@ -0,0 +1,74 @@
+use std::f64::consts::{FRAC_PI_2, PI};
+
+use crate::image::Image;
+use crate::modules::Point;
+
+/// Number of directions where the occlusion of a pixel is searched
+const DIRECTIONS: usize = 8;
+
+/// Screen space ambient occlusion
+///
+/// Darken the pixels of the image that are in a crease
+/// using the zbuffer returned by the render
+///
+/// For each pixel, walk radius pixels in some directions on the zbuffer
+/// searching for the biggest elevation that is hiding the pixel,
+/// the fraction of the sky that the pixel can see to the power of strength
+/// is the intensity of the light in the pixel
+pub fn ambient_occlusion(mut image: Image, zbuffer: &[f64], radius: i32, strength: f64) -> Image {
+    let width = image.width;
+    let height = image.height;
+    assert_eq!(
+        zbuffer.len(),
+        (width * height) as usize,
+        "SSAO: zbuffer length must be image.width * image.height"
+    );
+
+    let depth = |Point { x, y }: Point| -> Option<f64> {
+        if 0 <= x && x < width && 0 <= y && y < height {
+            Some(zbuffer[(y * width + x) as usize])
+        } else {
+            None
+        }
+    };
+
+    for y in 0..height {
+        for x in 0..width {
+            let p = Point { x, y };
+            let z = depth(p).unwrap();
+            if z == f64::NEG_INFINITY {
+                // Nothing was drawn in this pixel
+                continue;
+            }
+
+            let mut total = 0.0;
+            for i in 0..DIRECTIONS {
+                let angle = 2.0 * PI * (i as f64) / (DIRECTIONS as f64);
+                let (dx, dy) = (angle.cos(), angle.sin());
+
+                // Biggest angle between the pixel and other point in the zbuffer
+                let mut max_elevation: f64 = 0.0;
+                for t in 1..=radius {
+                    let t = t as f64;
+                    let q = Point {
+                        x: x + (dx * t).round() as i32,
+                        y: y + (dy * t).round() as i32,
+                    };
+                    let elevation = match depth(q) {
+                        Some(depth) => depth - z,
+                        None => break,
+                    };
+                    max_elevation = max_elevation.max((elevation / t).atan());
+                }
+
+                total += FRAC_PI_2 - max_elevation;
+            }
+            let intensity = (total / (FRAC_PI_2 * DIRECTIONS as f64)).powf(strength);
+
+            let color = image.get(p).unwrap();
+            image.set(p, color.light(intensity));
+        }
+    }
+
+    image
+}
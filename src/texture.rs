@@ -0,0 +1,228 @@
+use crate::image::{Blend, Image};
+use crate::modules::{Color, Point, Vector2};
+
+/// How the texels around a point of the texture are combined
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Filter {
+    /// The texel that contains the point
+    Nearest,
+    /// Interpolate the 4 closest texels
+    #[allow(dead_code)]
+    Bilinear,
+    /// Interpolate bilinear samples of the 2 closest levels
+    /// of the mipmap using the size of the pixel in the texture
+    Trilinear,
+}
+
+/// How the texture coordinates outside [0, 1] are mapped to the texture
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Wrap {
+    /// The texture is tiled
+    Repeat,
+    /// The texels of the border are extended
+    #[allow(dead_code)]
+    Clamp,
+    /// The texture is tiled and every other tile is mirrored
+    #[allow(dead_code)]
+    Mirror,
+}
+
+impl Wrap {
+    /// Map the texel index i to [0, size)
+    fn apply(self, i: i32, size: i32) -> i32 {
+        match self {
+            Self::Repeat => i.rem_euclid(size),
+            Self::Clamp => i.clamp(0, size - 1),
+            Self::Mirror => {
+                let i = i.rem_euclid(2 * size);
+                if i < size {
+                    i
+                } else {
+                    2 * size - 1 - i
+                }
+            }
+        }
+    }
+}
+
+/// How a texture is read
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Sampler {
+    pub filter: Filter,
+    pub wrap: Wrap,
+}
+
+/// Nearest texel and repeated texture
+impl Default for Sampler {
+    fn default() -> Self {
+        Self {
+            filter: Filter::Nearest,
+            wrap: Wrap::Repeat,
+        }
+    }
+}
+
+/// A point in a texture
+/// and the change of the point to the next pixels in x and y of the image,
+/// the size of the pixel in the texture
+#[derive(Clone, Copy, Debug)]
+pub struct TexturePoint {
+    pub t: Vector2,
+    pub dx: Vector2,
+    pub dy: Vector2,
+}
+
+/// A texture image with its mipmap
+///
+/// Each level of the mipmap is the previous level
+/// with half of the width and the height, until the size 1 x 1
+pub struct Texture {
+    levels: Vec<Image>,
+}
+
+impl Texture {
+    /// Generate the mipmap of the image,
+    /// each texel is the average of 2 x 2 texels of the previous level
+    ///
+    /// The image can't be empty
+    pub fn new(image: Image) -> Self {
+        assert!(
+            image.width > 0 && image.height > 0,
+            "A texture can't be an empty image {}x{}",
+            image.width,
+            image.height
+        );
+        let mut levels = vec![image];
+        loop {
+            let last = &levels[levels.len() - 1];
+            if last.width <= 1 && last.height <= 1 {
+                break;
+            }
+            let width = (last.width / 2).max(1);
+            let height = (last.height / 2).max(1);
+            let mut level = Image::new(width, height);
+            for y in 0..height {
+                for x in 0..width {
+                    // The last texel of an odd size is repeated
+                    let texel = |dx: i32, dy: i32| {
+                        let p = Point {
+                            x: (2 * x + dx).min(last.width - 1),
+                            y: (2 * y + dy).min(last.height - 1),
+                        };
+                        last.get(p).unwrap().to_rgba()
+                    };
+                    let texels = [texel(0, 0), texel(1, 0), texel(0, 1), texel(1, 1)];
+                    let average =
+                        std::array::from_fn(|k| texels.iter().map(|t| t[k]).sum::<f32>() / 4.0);
+                    level.set(Point { x, y }, Color::from_rgba(average));
+                }
+            }
+            levels.push(level);
+        }
+        Self { levels }
+    }
+
+    /// The original image
+    pub fn image(&self) -> &Image {
+        &self.levels[0]
+    }
+
+    /// Read the texture in the point using the sampler
+    pub fn sample(&self, sampler: Sampler, point: TexturePoint) -> Color {
+        match sampler.filter {
+            Filter::Nearest => {
+                let image = self.image();
+                let texel =
+                    |t: f64, size: i32| sampler.wrap.apply((t * size as f64).floor() as i32, size);
+                let p = Point {
+                    x: texel(point.t.x, image.width),
+                    y: texel(point.t.y, image.height),
+                };
+                image.get(p).unwrap()
+            }
+            Filter::Bilinear => Color::from_rgba(bilinear(self.image(), sampler.wrap, point.t)),
+            Filter::Trilinear => {
+                let level = self.level_of_detail(point);
+                let (low, high) = (level.floor() as usize, level.ceil() as usize);
+                let a = bilinear(&self.levels[low], sampler.wrap, point.t);
+                let b = bilinear(&self.levels[high], sampler.wrap, point.t);
+                let f = (level - level.floor()) as f32;
+                Color::from_rgba(std::array::from_fn(|k| a[k] + (b[k] - a[k]) * f))
+            }
+        }
+    }
+
+    /// Level of the mipmap where a texel has the size of the pixel,
+    /// in [0, number of levels - 1]
+    fn level_of_detail(&self, point: TexturePoint) -> f64 {
+        let image = self.image();
+        let texels = |d: Vector2| {
+            Vector2 {
+                x: d.x * image.width as f64,
+                y: d.y * image.height as f64,
+            }
+            .norm()
+        };
+        let size = texels(point.dx).max(texels(point.dy));
+        let last = (self.levels.len() - 1) as f64;
+        if size > 1.0 {
+            size.log2().min(last)
+        } else {
+            0.0
+        }
+    }
+}
+
+/// Interpolate the 4 texels closest to t,
+/// the centers of the texels are in ((x + 0.5) / width, (y + 0.5) / height)
+fn bilinear(image: &Image, wrap: Wrap, t: Vector2) -> [f32; 4] {
+    let x = t.x * image.width as f64 - 0.5;
+    let y = t.y * image.height as f64 - 0.5;
+    let (x0, y0) = (x.floor(), y.floor());
+    let (fx, fy) = ((x - x0) as f32, (y - y0) as f32);
+
+    // The cast saturates the huge coordinates, and the addition too
+    let texel = |dx: i32, dy: i32| {
+        let p = Point {
+            x: wrap.apply((x0 as i32).saturating_add(dx), image.width),
+            y: wrap.apply((y0 as i32).saturating_add(dy), image.height),
+        };
+        image.get(p).unwrap().to_rgba()
+    };
+    let (a, b, c, d) = (texel(0, 0), texel(1, 0), texel(0, 1), texel(1, 1));
+    std::array::from_fn(|k| {
+        let bottom = a[k] + (b[k] - a[k]) * fx;
+        let top = c[k] + (d[k] - c[k]) * fx;
+        bottom + (top - bottom) * fy
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sample_huge_coordinates() {
+        let texture = Texture::new(Image::new(4, 3).clear(Color::gray(100)));
+        let d = Vector2 { x: 0.01, y: 0.01 };
+        for filter in [Filter::Nearest, Filter::Bilinear, Filter::Trilinear] {
+            for wrap in [Wrap::Repeat, Wrap::Clamp, Wrap::Mirror] {
+                for c in [1e12, -1e12, 1e300, -1e300] {
+                    let point = TexturePoint {
+                        t: Vector2 { x: c, y: -c },
+                        dx: d,
+                        dy: d,
+                    };
+                    let color = texture.sample(Sampler { filter, wrap }, point);
+                    assert_eq!(color, Color::gray(100));
+                }
+            }
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "empty image")]
+    fn empty_image() {
+        Texture::new(Image::new(0, 4));
+    }
+}
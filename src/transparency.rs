@@ -0,0 +1,66 @@
+use crate::image::{Blend, BlendMode, Image, OitBuffer};
+use crate::shader::{ShadedFace, Shader};
+
+/// How the transparent faces are drawn after the opaque faces
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Transparency {
+    /// The faces are sorted from the farthest to the closest
+    /// and blended with the equation
+    Sorted(BlendMode),
+    /// Weighted blended order-independent transparency,
+    /// the faces aren't sorted and the result is an approximation
+    WeightedBlended,
+}
+
+impl Default for Transparency {
+    fn default() -> Self {
+        Self::Sorted(BlendMode::Alpha)
+    }
+}
+
+/// Draw the transparent faces over the opaque faces in the image
+///
+/// The faces are tested with the zbuffer of the opaque faces,
+/// but they don't change it
+pub fn draw_transparent<P, S>(
+    image: &mut Image<P>,
+    zbuffer: &mut [f64],
+    shaders: &mut [S],
+    faces: Vec<ShadedFace>,
+    transparency: Transparency,
+) where
+    P: Blend,
+    S: Shader<P>,
+{
+    match transparency {
+        Transparency::Sorted(mode) => {
+            // Depth of the center of the face in the normalized device coordinates
+            // (the farthest faces have the smaller depth)
+            let mut faces: Vec<(f64, ShadedFace)> = faces
+                .into_iter()
+                .map(|face| {
+                    let (u, v, w) = face.vertex_stage(shaders);
+                    let z = u.z + v.z + w.z;
+                    let w = u.w + v.w + w.w;
+                    (z / w, face)
+                })
+                .collect();
+            faces.sort_by(|(a, _), (b, _)| a.total_cmp(b));
+
+            for (_, face) in faces {
+                let triangle = face.vertex_stage(shaders);
+                let shader = &shaders[face.shader];
+                image.triangle_shader_blend(zbuffer, triangle, shader, face.cull_mode, mode);
+            }
+        }
+        Transparency::WeightedBlended => {
+            let mut oit = OitBuffer::new(image);
+            for face in faces {
+                let triangle = face.vertex_stage(shaders);
+                let shader = &shaders[face.shader];
+                image.triangle_shader_oit(zbuffer, &mut oit, triangle, shader, face.cull_mode);
+            }
+            oit.resolve(image);
+        }
+    }
+}